@@ -0,0 +1,176 @@
+#[cfg(feature = "cli")]
+use crate::tree::TreeOptions;
+use crate::{Sha224, Sha256, Sha384, Sha512, Sha512_224, Sha512_256};
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Evaluate `$body` with `$hasher` as the hasher type of the algorithm
+macro_rules! with_hasher {
+    ($algorithm:expr, $hasher:ident => $body:expr) => {
+        match $algorithm {
+            Algorithm::Sha224 => {
+                type $hasher = Sha224;
+                $body
+            }
+            Algorithm::Sha256 => {
+                type $hasher = Sha256;
+                $body
+            }
+            Algorithm::Sha384 => {
+                type $hasher = Sha384;
+                $body
+            }
+            Algorithm::Sha512 => {
+                type $hasher = Sha512;
+                $body
+            }
+            Algorithm::Sha512_224 => {
+                type $hasher = Sha512_224;
+                $body
+            }
+            Algorithm::Sha512_256 => {
+                type $hasher = Sha512_256;
+                $body
+            }
+        }
+    };
+}
+
+/// The fixed size SHA2 algorithms, for choosing one at runtime
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Algorithm {
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha512_224,
+    Sha512_256,
+}
+
+impl Algorithm {
+    /// Every algorithm, from the shortest output to the longest
+    pub const ALL: [Algorithm; 6] = [
+        Self::Sha224,
+        Self::Sha512_224,
+        Self::Sha256,
+        Self::Sha512_256,
+        Self::Sha384,
+        Self::Sha512,
+    ];
+
+    /// Look up an algorithm by name, ignoring case
+    ///
+    /// Accepts the names of the coreutils tools without "sum" (`sha256`), and the tags of BSD
+    /// style checksum lines (`SHA256`, `SHA512t256`). SHA-512/t is also accepted as `sha512-256`
+    /// and `sha512/256`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "sha224" => Self::Sha224,
+            "sha256" => Self::Sha256,
+            "sha384" => Self::Sha384,
+            "sha512" => Self::Sha512,
+            "sha512-224" | "sha512/224" | "sha512t224" => Self::Sha512_224,
+            "sha512-256" | "sha512/256" | "sha512t256" => Self::Sha512_256,
+            _ => return None,
+        })
+    }
+
+    /// The tag of the algorithm in BSD style checksum lines
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::Sha224 => "SHA224",
+            Self::Sha256 => "SHA256",
+            Self::Sha384 => "SHA384",
+            Self::Sha512 => "SHA512",
+            Self::Sha512_224 => "SHA512t224",
+            Self::Sha512_256 => "SHA512t256",
+        }
+    }
+
+    /// The size of the hash, in bytes
+    pub fn output_size(self) -> usize {
+        match self {
+            Self::Sha224 | Self::Sha512_224 => 28,
+            Self::Sha256 | Self::Sha512_256 => 32,
+            Self::Sha384 => 48,
+            Self::Sha512 => 64,
+        }
+    }
+
+    /// Guess the algorithm from the size of a hash, in bytes
+    ///
+    /// The SHA-512/t truncations have the same sizes as SHA224 and SHA256, which are preferred.
+    pub fn from_output_size(size: usize) -> Option<Self> {
+        Some(match size {
+            28 => Self::Sha224,
+            32 => Self::Sha256,
+            48 => Self::Sha384,
+            64 => Self::Sha512,
+            _ => return None,
+        })
+    }
+
+    /// Hash everything the reader returns, see [`hash_reader`](crate::io::hash_reader)
+    pub fn hash_reader(self, reader: impl Read) -> io::Result<Vec<u8>> {
+        with_hasher!(self, D => Ok(crate::io::hash_reader::<D>(reader)?.0.to_vec()))
+    }
+
+    /// Hash the contents of a file, see [`hash_file`](crate::io::hash_file)
+    pub fn hash_file(self, path: &Path) -> io::Result<Vec<u8>> {
+        with_hasher!(self, D => Ok(crate::io::hash_file::<D>(path)?.to_vec()))
+    }
+
+    /// Hash a file by memory mapping it, see [`hash_file_mmap`](crate::io::hash_file_mmap)
+    #[cfg(feature = "mmap")]
+    pub fn hash_file_mmap(self, path: &Path) -> io::Result<Vec<u8>> {
+        with_hasher!(self, D => Ok(crate::io::hash_file_mmap::<D>(path)?.to_vec()))
+    }
+
+    /// Hash files on worker threads, see
+    /// [`hash_files_parallel_with`](crate::io::hash_files_parallel_with)
+    pub fn hash_files_parallel_with<P: AsRef<Path> + Sync>(
+        self,
+        paths: &[P],
+        threads: usize,
+        mut on_result: impl FnMut(usize, io::Result<Vec<u8>>),
+    ) {
+        with_hasher!(self, D => crate::io::hash_files_parallel_with::<D>(
+            paths,
+            threads,
+            |i, result| on_result(i, result.map(|hash| hash.to_vec())),
+        ))
+    }
+
+    /// The digest of a directory tree, see [`hash_tree`](crate::tree::hash_tree)
+    #[cfg(feature = "cli")]
+    pub fn hash_tree(self, root: &Path, options: &TreeOptions) -> io::Result<Vec<u8>> {
+        with_hasher!(self, D => Ok(crate::tree::hash_tree::<D>(root, options)?.to_vec()))
+    }
+}
+
+/// Tests for choosing algorithms at runtime
+#[cfg(test)]
+mod tests {
+    use super::Algorithm;
+    use digest::Digest;
+
+    // Names and tags lead back to the algorithm, and the sizes match the hashers
+    #[test]
+    fn names_and_sizes() {
+        for algorithm in Algorithm::ALL {
+            assert_eq!(Algorithm::from_name(algorithm.tag()), Some(algorithm));
+            let hash = algorithm.hash_reader(&b"hello world"[..]).unwrap();
+            assert_eq!(hash.len(), algorithm.output_size());
+        }
+        assert_eq!(
+            Algorithm::from_name("sha512/256"),
+            Some(Algorithm::Sha512_256)
+        );
+        assert_eq!(Algorithm::from_name("md5"), None);
+        assert_eq!(Algorithm::from_output_size(32), Some(Algorithm::Sha256));
+        assert_eq!(
+            Algorithm::Sha384.hash_reader(&b"abc"[..]).unwrap()[..],
+            sha2::Sha384::digest(b"abc")[..]
+        );
+    }
+}
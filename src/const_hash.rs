@@ -0,0 +1,140 @@
+//! Hashing in constant evaluation, to embed digests as constants without a build script
+//!
+//! The functions hash a single byte string, and the builders hash a concatenation of several:
+//!
+//! ```
+//! use sha_crypto::const_hash::{sha256, ConstSha256};
+//!
+//! const CONFIG: [u8; 32] = sha256(b"retries = 3\n");
+//! const ASSET: [u8; 32] = ConstSha256::new()
+//!     .update(b"assets/")
+//!     .update(b"logo.png")
+//!     .finalize();
+//! assert_eq!(ASSET, sha256(b"assets/logo.png"));
+//! ```
+//!
+//! Everything uses the portable compression function, the same one the hashers fall back on
+//! at runtime.
+
+use crate::engine256::Engine256;
+use crate::engine512::Engine512;
+use crate::{sha224, sha256, sha384, sha512};
+
+/// Define a builder and a function hashing in constant evaluation
+macro_rules! const_hasher {
+    ($name:ident, $function:ident, $engine:ty, $iv:expr, $size:literal, $algorithm:literal) => {
+        #[doc = concat!("Builder hashing a concatenation of byte strings with ", $algorithm)]
+        #[doc = "in constant evaluation"]
+        #[derive(Clone)]
+        pub struct $name {
+            engine: $engine,
+        }
+
+        impl $name {
+            /// Start hashing an empty message
+            pub const fn new() -> Self {
+                Self {
+                    engine: <$engine>::new($iv),
+                }
+            }
+
+            /// Append data to the message
+            ///
+            /// Panics if the message gets longer than the algorithm allows, which fails the build
+            /// in constant evaluation.
+            pub const fn update(mut self, data: &[u8]) -> Self {
+                self.engine.update_const(data);
+                self
+            }
+
+            /// Pad the message and output the hash
+            pub const fn finalize(self) -> [u8; $size] {
+                let mut out = [0; $size];
+                self.engine.finalize_const(&mut out);
+                out
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        #[doc = concat!("The ", $algorithm, " hash of `data`, usable in constant evaluation")]
+        pub const fn $function(data: &[u8]) -> [u8; $size] {
+            $name::new().update(data).finalize()
+        }
+    };
+}
+
+const_hasher!(ConstSha224, sha224, Engine256, sha224::IV, 28, "SHA224");
+const_hasher!(ConstSha256, sha256, Engine256, sha256::IV, 32, "SHA256");
+const_hasher!(ConstSha384, sha384, Engine512, sha384::IV, 48, "SHA384");
+const_hasher!(ConstSha512, sha512, Engine512, sha512::IV, 64, "SHA512");
+
+/// Tests for the constant hashing functions
+#[cfg(test)]
+mod tests {
+    use super::{sha224, sha256, sha384, sha512, ConstSha256, ConstSha512};
+    use crate::test_util::hex;
+    use digest::Digest;
+
+    // Evaluated at compile time
+    const ABC: [u8; 32] = sha256(b"abc");
+    const CONCATENATED: [u8; 64] = ConstSha512::new().update(b"a").update(b"bc").finalize();
+
+    // The constants match the known hashes
+    #[test]
+    fn constants() {
+        assert_eq!(
+            ABC[..],
+            hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(CONCATENATED, sha512(b"abc"));
+        assert_eq!(
+            CONCATENATED[..],
+            hex(concat!(
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a",
+                "2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+            ))
+        );
+    }
+
+    // Every length around the padding boundaries hashes like the hashers
+    #[test]
+    fn lengths() {
+        let buffer: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        for len in 0..buffer.len() {
+            let data = &buffer[..len];
+            assert_eq!(
+                sha224(data),
+                sha2::Sha224::digest(data)[..],
+                "{} bytes",
+                len
+            );
+            assert_eq!(
+                sha256(data),
+                sha2::Sha256::digest(data)[..],
+                "{} bytes",
+                len
+            );
+            assert_eq!(
+                sha384(data),
+                sha2::Sha384::digest(data)[..],
+                "{} bytes",
+                len
+            );
+            assert_eq!(
+                sha512(data),
+                sha2::Sha512::digest(data)[..],
+                "{} bytes",
+                len
+            );
+
+            let (first, second) = data.split_at(len / 3);
+            let split = ConstSha256::new().update(first).update(second).finalize();
+            assert_eq!(split, sha256(data));
+        }
+    }
+}
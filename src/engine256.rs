@@ -0,0 +1,347 @@
+#[cfg(feature = "alloc")]
+use crate::state::{HashState, InvalidState};
+use crate::LengthOverflow;
+
+/// Hardware accelerated compression using the x86 SHA extensions
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(feature = "force-soft")
+))]
+mod x86;
+
+/// The shared state of every hash built on the SHA256 compression function (32-bit words)
+///
+/// The variants only differ by their initial hash values and by how much of the final state is
+/// output, so the public hashers are thin wrappers around this engine.
+#[derive(Clone)]
+pub(crate) struct Engine256 {
+    h: [u32; 8],
+    buffer: [u8; 64],
+    filled: u8,
+    /// The number of bits of the message in `buffer[filled]`, when it ends with a partial byte
+    partial: u8,
+    /// The length of the message in bits
+    length: u64,
+}
+
+/// Array of round constants. They are the first 32 bits of the cube roots of the first 64 primes in hex form
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+impl Engine256 {
+    /// Create a new engine starting from the given initial hash values
+    pub(crate) const fn new(iv: [u32; 8]) -> Self {
+        Self {
+            h: iv,
+            buffer: [0; 64],
+            filled: 0,
+            partial: 0,
+            length: 0,
+        }
+    }
+
+    /// [`Self::try_update`], panicking if the message gets too long
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        if let Err(e) = self.try_update(data) {
+            panic!("{}", e)
+        }
+    }
+
+    /// Updates the length, buffer and filled variables, running the rounds for every full block
+    ///
+    /// Fails without hashing anything if the message would reach `2^64` bits, which the length
+    /// in the padding can't represent.
+    pub(crate) fn try_update(&mut self, data: &[u8]) -> Result<(), LengthOverflow> {
+        self.add_length((data.len() as u64).checked_mul(8))?;
+        self.absorb(data);
+        Ok(())
+    }
+
+    /// Count `bits` more bits of message, failing if the length overflows
+    fn add_length(&mut self, bits: Option<u64>) -> Result<(), LengthOverflow> {
+        self.length = bits
+            .and_then(|bits| self.length.checked_add(bits))
+            .ok_or(LengthOverflow)?;
+        Ok(())
+    }
+
+    /// Add whole bytes to the buffer, without counting them in the length
+    ///
+    /// Full blocks are compressed straight from `data`, only the incomplete block at the end is
+    /// copied into the buffer.
+    fn absorb(&mut self, mut data: &[u8]) {
+        // After a partial byte, every byte has to be shifted into place
+        if self.partial > 0 {
+            data.iter().for_each(|&byte| self.push_bits(byte, 8));
+            return;
+        }
+
+        // Complete the block in the buffer first
+        if self.filled > 0 {
+            let empty = &mut self.buffer[self.filled as usize..];
+            let len = data.len().min(empty.len());
+            empty[..len].copy_from_slice(&data[..len]);
+            data = &data[len..];
+            if len < empty.len() {
+                self.filled += len as u8;
+                return;
+            }
+            compress(&mut self.h, core::slice::from_ref(&self.buffer));
+        }
+
+        let (blocks, rest) = data.as_chunks::<64>();
+        compress(&mut self.h, blocks);
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.filled = rest.len() as u8;
+    }
+
+    /// Add the first `bit_len` bits of `data` to the message, the bits of each byte starting
+    /// from the most significant one
+    ///
+    /// Panics if `data` has less than `bit_len` bits, or if the message gets too long.
+    pub(crate) fn update_bits(&mut self, data: &[u8], bit_len: usize) {
+        assert!(bit_len <= data.len() * 8, "bit_len is longer than the data");
+        if let Err(e) = self.add_length(Some(bit_len as u64)) {
+            panic!("{}", e)
+        }
+        let (bytes, bits) = (bit_len / 8, bit_len % 8);
+        self.absorb(&data[..bytes]);
+        if bits > 0 {
+            self.push_bits(data[bytes] & !(0xff >> bits), bits as u8);
+        }
+    }
+
+    /// Add the `count` high bits of `byte` to the message, the low bits being zero
+    fn push_bits(&mut self, byte: u8, count: u8) {
+        // The bits go right after the ones of the partial byte, and may spill into the next one
+        let current = if self.partial > 0 {
+            self.buffer[self.filled as usize]
+        } else {
+            0
+        };
+        let bits = (current as u16) << 8 | (byte as u16) << (8 - self.partial);
+        self.buffer[self.filled as usize] = (bits >> 8) as u8;
+        self.partial += count;
+        if self.partial >= 8 {
+            self.partial -= 8;
+            self.filled += 1;
+            if self.filled == 64 {
+                compress(&mut self.h, core::slice::from_ref(&self.buffer));
+                self.filled = 0;
+            }
+            self.buffer[self.filled as usize] = bits as u8;
+        }
+    }
+
+    /// Export the state, for an algorithm with the given output size
+    #[cfg(feature = "alloc")]
+    pub(crate) fn to_state(&self, output_bits: u16) -> HashState {
+        HashState {
+            word_bits: 32,
+            output_bits,
+            h: self.h.map(|h| h as u64),
+            length: self.length as u128,
+            buffer: self.buffer[..self.filled as usize + (self.partial > 0) as usize].to_vec(),
+        }
+    }
+
+    /// Restore an exported state, checking that it belongs to an algorithm with this output size
+    #[cfg(feature = "alloc")]
+    pub(crate) fn from_state(state: &HashState, output_bits: u16) -> Result<Self, InvalidState> {
+        if state.word_bits != 32 || state.output_bits != output_bits {
+            return Err(InvalidState::WrongAlgorithm);
+        }
+        if state.h.iter().any(|&h| h > u32::MAX as u64) || state.length > u64::MAX as u128 {
+            return Err(InvalidState::OutOfRange);
+        }
+
+        // The buffer holds whatever is left of the message after the last full block, with the
+        // unused bits of a partial last byte cleared
+        let bits = (state.length % 512) as usize;
+        let partial = (bits % 8) as u8;
+        if state.buffer.len() != bits.div_ceil(8)
+            || (partial > 0 && state.buffer[bits / 8] & (0xff >> partial) != 0)
+        {
+            return Err(InvalidState::InvalidBuffer);
+        }
+
+        let mut engine = Self::new(state.h.map(|h| h as u32));
+        engine.buffer[..state.buffer.len()].copy_from_slice(&state.buffer);
+        engine.filled = (bits / 8) as u8;
+        engine.partial = partial;
+        engine.length = state.length as u64;
+        Ok(engine)
+    }
+
+    /// Pad the message and write the first `out.len()` bytes of the final hash value to `out`
+    ///
+    /// Never fails: the length was checked by every update, and the padding isn't counted in it.
+    pub(crate) fn finalize_into(mut self, out: &mut [u8]) {
+        let (padding, len) = self.padding();
+        self.absorb(&padding[..len]);
+        self.output(out);
+    }
+
+    /// [`Self::update`] for constant evaluation, always with the portable compression function
+    ///
+    /// The message never has a partial byte, since the constant hashers only take whole bytes.
+    pub(crate) const fn update_const(&mut self, data: &[u8]) {
+        let bits = match (data.len() as u64).checked_mul(8) {
+            Some(bits) => self.length.checked_add(bits),
+            None => None,
+        };
+        match bits {
+            Some(bits) => self.length = bits,
+            None => panic!("message is too long for the hash algorithm"),
+        }
+        self.absorb_const(data);
+    }
+
+    /// [`Self::absorb`] for constant evaluation, on whole bytes only
+    const fn absorb_const(&mut self, data: &[u8]) {
+        let mut i = 0;
+        while i < data.len() {
+            self.buffer[self.filled as usize] = data[i];
+            self.filled += 1;
+            if self.filled == 64 {
+                compress_soft(&mut self.h, &self.buffer);
+                self.filled = 0;
+            }
+            i += 1;
+        }
+    }
+
+    /// [`Self::finalize_into`] for constant evaluation
+    pub(crate) const fn finalize_const(mut self, out: &mut [u8]) {
+        let (padding, len) = self.padding();
+        self.absorb_const(padding.split_at(len).0);
+        self.output(out);
+    }
+
+    /// The padding that completes the message: a one bit, zeros and the length in bits
+    ///
+    /// Returns the padding and its length, the first `len` bytes fill the buffer up to the end
+    /// of a block, with a second block when there is no room for the length in this one. When
+    /// the message ends with a partial byte, the one bit goes right after its last bit, and the
+    /// byte is taken out of the buffer to start the padding.
+    const fn padding(&mut self) -> ([u8; 128], usize) {
+        let mut padding = [0; 128];
+        padding[0] = 0b10000000 >> self.partial;
+        if self.partial > 0 {
+            padding[0] |= self.buffer[self.filled as usize];
+            self.partial = 0;
+        }
+        let len = if self.filled < 64 - 8 {
+            64 - self.filled as usize
+        } else {
+            128 - self.filled as usize
+        };
+        let length = self.length.to_be_bytes();
+        let mut i = 0;
+        while i < 8 {
+            padding[len - 8 + i] = length[i];
+            i += 1;
+        }
+        (padding, len)
+    }
+
+    /// Write the first `out.len()` bytes of the hash value, in big endian, to `out`
+    const fn output(&self, out: &mut [u8]) {
+        let mut i = 0;
+        while i < out.len() {
+            out[i] = (self.h[i / 4] >> (24 - 8 * (i % 4))) as u8;
+            i += 1;
+        }
+    }
+}
+
+/// Update the hash value (state) using each block of the message in turn
+///
+/// Uses the SHA extensions when the CPU supports them.
+fn compress(state: &mut [u32; 8], blocks: &[[u8; 64]]) {
+    #[cfg(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        not(feature = "force-soft")
+    ))]
+    if x86::available() {
+        // Safety: the CPU supports the required instructions
+        unsafe { x86::compress(state, blocks) };
+        return;
+    }
+    blocks.iter().for_each(|block| compress_soft(state, block));
+}
+
+/// Update the hash value using one block of the message, with the portable implementation
+///
+/// Only uses `while` loops, so the constant hashing functions share it.
+const fn compress_soft(state: &mut [u32; 8], block: &[u8; 64]) {
+    // Create a 64-entry message schedule array w[0..63] of 32-bit words
+    let mut w = [0u32; 64];
+    // The initial values in w[0..63] don't matter, so many implementations zero them here
+    // Copy chunk into first 16 words w[0..15] of the message schedule array
+    let mut i = 0;
+    while i < 16 {
+        let (buf, _) = block.split_at(4 * i).1.split_first_chunk::<4>().unwrap();
+        w[i] = u32::from_be_bytes(*buf);
+        i += 1;
+    }
+
+    // Extend the first 16 words into the remaining 48 words w[16..63] of the message schedule array:
+    while i < 64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+        i += 1;
+    }
+
+    // Initialize working variables to current hash value:
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+
+    // Compression function main loop:
+    let mut i = 0;
+    while i < 64 {
+        // Perform linear manipulation to shuffle the data further
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ ((!e) & g);
+        let temp1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+
+        // Assign results to temp variables
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+        i += 1;
+    }
+    // Add the compressed chunk to the current hash value:
+    state[0] = state[0].wrapping_add(a);
+    state[1] = state[1].wrapping_add(b);
+    state[2] = state[2].wrapping_add(c);
+    state[3] = state[3].wrapping_add(d);
+    state[4] = state[4].wrapping_add(e);
+    state[5] = state[5].wrapping_add(f);
+    state[6] = state[6].wrapping_add(g);
+    state[7] = state[7].wrapping_add(h);
+}
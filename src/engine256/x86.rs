@@ -0,0 +1,122 @@
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+/// Check whether the CPU supports the instructions used by [`compress`]
+#[cfg(feature = "std")]
+pub(super) fn available() -> bool {
+    is_x86_feature_detected!("sha")
+        && is_x86_feature_detected!("sse2")
+        && is_x86_feature_detected!("ssse3")
+        && is_x86_feature_detected!("sse4.1")
+}
+
+/// Check whether the instructions used by [`compress`] are enabled for the whole build
+///
+/// Without `std` the CPU can't be queried at runtime, so the extensions are only used when the
+/// target guarantees them, eg. with `-C target-cpu=native`.
+#[cfg(not(feature = "std"))]
+pub(super) fn available() -> bool {
+    cfg!(all(
+        target_feature = "sha",
+        target_feature = "sse2",
+        target_feature = "ssse3",
+        target_feature = "sse4.1"
+    ))
+}
+
+/// Compute the next four words of the message schedule from the previous sixteen
+#[inline(always)]
+unsafe fn schedule(w0: __m128i, w1: __m128i, w2: __m128i, w3: __m128i) -> __m128i {
+    let t1 = _mm_sha256msg1_epu32(w0, w1);
+    let t2 = _mm_alignr_epi8(w3, w2, 4);
+    let t3 = _mm_add_epi32(t1, t2);
+    _mm_sha256msg2_epu32(t3, w3)
+}
+
+/// Update the hash value using each block of the message in turn, with the SHA extensions
+///
+/// # Safety
+///
+/// The CPU must support the instructions, which [`available`] checks
+#[target_feature(enable = "sha,sse2,ssse3,sse4.1")]
+pub(super) unsafe fn compress(state: &mut [u32; 8], blocks: &[[u8; 64]]) {
+    // Swaps the bytes of every 32-bit word, to read the message as big endian
+    let mask = _mm_set_epi64x(0x0c0d_0e0f_0809_0a0b, 0x0405_0607_0001_0203);
+
+    // The instructions want the state as ABEF and CDGH, instead of ABCD and EFGH
+    let state_ptr = state.as_ptr() as *const __m128i;
+    let dcba = _mm_loadu_si128(state_ptr);
+    let efgh = _mm_loadu_si128(state_ptr.add(1));
+    let cdab = _mm_shuffle_epi32(dcba, 0xb1);
+    let efgh = _mm_shuffle_epi32(efgh, 0x1b);
+    let mut abef = _mm_alignr_epi8(cdab, efgh, 8);
+    let mut cdgh = _mm_blend_epi16(efgh, cdab, 0xf0);
+    for block in blocks {
+        let abef_save = abef;
+        let cdgh_save = cdgh;
+
+        // The last sixteen words of the message schedule, four per register
+        let block_ptr = block.as_ptr() as *const __m128i;
+        let mut w = [
+            _mm_shuffle_epi8(_mm_loadu_si128(block_ptr), mask),
+            _mm_shuffle_epi8(_mm_loadu_si128(block_ptr.add(1)), mask),
+            _mm_shuffle_epi8(_mm_loadu_si128(block_ptr.add(2)), mask),
+            _mm_shuffle_epi8(_mm_loadu_si128(block_ptr.add(3)), mask),
+        ];
+
+        // Four rounds at a time, two per instruction
+        for i in 0..16 {
+            if i >= 4 {
+                w[i % 4] = schedule(w[i % 4], w[(i + 1) % 4], w[(i + 2) % 4], w[(i + 3) % 4]);
+            }
+            let k = _mm_loadu_si128(super::K.as_ptr().add(i * 4) as *const __m128i);
+            let wk = _mm_add_epi32(w[i % 4], k);
+            cdgh = _mm_sha256rnds2_epu32(cdgh, abef, wk);
+            abef = _mm_sha256rnds2_epu32(abef, cdgh, _mm_shuffle_epi32(wk, 0x0e));
+        }
+
+        // Add the compressed chunk to the current hash value, and put it back in order
+        abef = _mm_add_epi32(abef, abef_save);
+        cdgh = _mm_add_epi32(cdgh, cdgh_save);
+    }
+
+    let feba = _mm_shuffle_epi32(abef, 0x1b);
+    let dchg = _mm_shuffle_epi32(cdgh, 0xb1);
+    let dcba = _mm_blend_epi16(feba, dchg, 0xf0);
+    let hgef = _mm_alignr_epi8(dchg, feba, 8);
+    let state_ptr = state.as_mut_ptr() as *mut __m128i;
+    _mm_storeu_si128(state_ptr, dcba);
+    _mm_storeu_si128(state_ptr.add(1), hgef);
+}
+
+/// Tests comparing the accelerated and portable implementations
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    // Compress runs of random blocks into random states with both implementations. Without the
+    // extensions there is nothing to compare, and the skip is reported.
+    #[test]
+    fn matches_soft() {
+        if !super::available() {
+            eprintln!("Skipping matches_soft: the CPU doesn't have the SHA extensions");
+            return;
+        }
+        let mut rng = rand::thread_rng();
+        for i in 0..1000 {
+            let mut blocks = vec![[0u8; 64]; i % 4];
+            blocks.iter_mut().for_each(|block| rng.fill(&mut block[..]));
+            let state: [u32; 8] = rng.gen();
+
+            let mut soft = state;
+            blocks
+                .iter()
+                .for_each(|block| crate::engine256::compress_soft(&mut soft, block));
+            let mut accelerated = state;
+            unsafe { super::compress(&mut accelerated, &blocks) };
+            assert_eq!(soft, accelerated, "Failed on blocks {:02x?}", blocks);
+        }
+    }
+}
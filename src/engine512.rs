@@ -0,0 +1,399 @@
+#[cfg(feature = "alloc")]
+use crate::state::{HashState, InvalidState};
+use crate::LengthOverflow;
+
+/// The shared state of every hash built on the SHA512 compression function (64-bit words)
+///
+/// The variants only differ by their initial hash values and by how much of the final state is
+/// output, so the public hashers are thin wrappers around this engine.
+#[derive(Clone)]
+pub(crate) struct Engine512 {
+    h: [u64; 8],
+    buffer: [u8; 128],
+    filled: u8,
+    /// The number of bits of the message in `buffer[filled]`, when it ends with a partial byte
+    partial: u8,
+    /// The length of the message in bits
+    length: u128,
+}
+
+/// Array of round constants. They are the first 64 bits of the cube roots of the first 64 primes in hex form
+const K: [u64; 80] = [
+    0x428a2f98d728ae22,
+    0x7137449123ef65cd,
+    0xb5c0fbcfec4d3b2f,
+    0xe9b5dba58189dbbc,
+    0x3956c25bf348b538,
+    0x59f111f1b605d019,
+    0x923f82a4af194f9b,
+    0xab1c5ed5da6d8118,
+    0xd807aa98a3030242,
+    0x12835b0145706fbe,
+    0x243185be4ee4b28c,
+    0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f,
+    0x80deb1fe3b1696b1,
+    0x9bdc06a725c71235,
+    0xc19bf174cf692694,
+    0xe49b69c19ef14ad2,
+    0xefbe4786384f25e3,
+    0x0fc19dc68b8cd5b5,
+    0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275,
+    0x4a7484aa6ea6e483,
+    0x5cb0a9dcbd41fbd4,
+    0x76f988da831153b5,
+    0x983e5152ee66dfab,
+    0xa831c66d2db43210,
+    0xb00327c898fb213f,
+    0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2,
+    0xd5a79147930aa725,
+    0x06ca6351e003826f,
+    0x142929670a0e6e70,
+    0x27b70a8546d22ffc,
+    0x2e1b21385c26c926,
+    0x4d2c6dfc5ac42aed,
+    0x53380d139d95b3df,
+    0x650a73548baf63de,
+    0x766a0abb3c77b2a8,
+    0x81c2c92e47edaee6,
+    0x92722c851482353b,
+    0xa2bfe8a14cf10364,
+    0xa81a664bbc423001,
+    0xc24b8b70d0f89791,
+    0xc76c51a30654be30,
+    0xd192e819d6ef5218,
+    0xd69906245565a910,
+    0xf40e35855771202a,
+    0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8,
+    0x1e376c085141ab53,
+    0x2748774cdf8eeb99,
+    0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63,
+    0x4ed8aa4ae3418acb,
+    0x5b9cca4f7763e373,
+    0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc,
+    0x78a5636f43172f60,
+    0x84c87814a1f0ab72,
+    0x8cc702081a6439ec,
+    0x90befffa23631e28,
+    0xa4506cebde82bde9,
+    0xbef9a3f7b2c67915,
+    0xc67178f2e372532b,
+    0xca273eceea26619c,
+    0xd186b8c721c0c207,
+    0xeada7dd6cde0eb1e,
+    0xf57d4f7fee6ed178,
+    0x06f067aa72176fba,
+    0x0a637dc5a2c898a6,
+    0x113f9804bef90dae,
+    0x1b710b35131c471b,
+    0x28db77f523047d84,
+    0x32caab7b40c72493,
+    0x3c9ebe0a15c9bebc,
+    0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6,
+    0x597f299cfc657e2a,
+    0x5fcb6fab3ad6faec,
+    0x6c44198c4a475817,
+];
+
+impl Engine512 {
+    /// Create a new engine starting from the given initial hash values
+    pub(crate) const fn new(iv: [u64; 8]) -> Self {
+        Self {
+            h: iv,
+            buffer: [0; 128],
+            filled: 0,
+            partial: 0,
+            length: 0,
+        }
+    }
+
+    /// [`Self::try_update`], panicking if the message gets too long
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        if let Err(e) = self.try_update(data) {
+            panic!("{}", e)
+        }
+    }
+
+    /// Updates the length, buffer and filled variables, running the rounds for every full block
+    ///
+    /// Fails without hashing anything if the message would reach `2^128` bits, which the length
+    /// in the padding can't represent.
+    pub(crate) fn try_update(&mut self, data: &[u8]) -> Result<(), LengthOverflow> {
+        self.add_length((data.len() as u128).checked_mul(8))?;
+        self.absorb(data);
+        Ok(())
+    }
+
+    /// Count `bits` more bits of message, failing if the length overflows
+    fn add_length(&mut self, bits: Option<u128>) -> Result<(), LengthOverflow> {
+        self.length = bits
+            .and_then(|bits| self.length.checked_add(bits))
+            .ok_or(LengthOverflow)?;
+        Ok(())
+    }
+
+    /// Add whole bytes to the buffer, without counting them in the length
+    ///
+    /// Full blocks are compressed straight from `data`, only the incomplete block at the end is
+    /// copied into the buffer.
+    fn absorb(&mut self, mut data: &[u8]) {
+        // After a partial byte, every byte has to be shifted into place
+        if self.partial > 0 {
+            data.iter().for_each(|&byte| self.push_bits(byte, 8));
+            return;
+        }
+
+        // Complete the block in the buffer first
+        if self.filled > 0 {
+            let empty = &mut self.buffer[self.filled as usize..];
+            let len = data.len().min(empty.len());
+            empty[..len].copy_from_slice(&data[..len]);
+            data = &data[len..];
+            if len < empty.len() {
+                self.filled += len as u8;
+                return;
+            }
+            compress(&mut self.h, core::slice::from_ref(&self.buffer));
+        }
+
+        let (blocks, rest) = data.as_chunks::<128>();
+        compress(&mut self.h, blocks);
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.filled = rest.len() as u8;
+    }
+
+    /// Add the first `bit_len` bits of `data` to the message, the bits of each byte starting
+    /// from the most significant one
+    ///
+    /// Panics if `data` has less than `bit_len` bits, or if the message gets too long.
+    pub(crate) fn update_bits(&mut self, data: &[u8], bit_len: usize) {
+        assert!(bit_len <= data.len() * 8, "bit_len is longer than the data");
+        if let Err(e) = self.add_length(Some(bit_len as u128)) {
+            panic!("{}", e)
+        }
+        let (bytes, bits) = (bit_len / 8, bit_len % 8);
+        self.absorb(&data[..bytes]);
+        if bits > 0 {
+            self.push_bits(data[bytes] & !(0xff >> bits), bits as u8);
+        }
+    }
+
+    /// Add the `count` high bits of `byte` to the message, the low bits being zero
+    fn push_bits(&mut self, byte: u8, count: u8) {
+        // The bits go right after the ones of the partial byte, and may spill into the next one
+        let current = if self.partial > 0 {
+            self.buffer[self.filled as usize]
+        } else {
+            0
+        };
+        let bits = (current as u16) << 8 | (byte as u16) << (8 - self.partial);
+        self.buffer[self.filled as usize] = (bits >> 8) as u8;
+        self.partial += count;
+        if self.partial >= 8 {
+            self.partial -= 8;
+            self.filled += 1;
+            if self.filled == 128 {
+                compress(&mut self.h, core::slice::from_ref(&self.buffer));
+                self.filled = 0;
+            }
+            self.buffer[self.filled as usize] = bits as u8;
+        }
+    }
+
+    /// Export the state, for an algorithm with the given output size
+    #[cfg(feature = "alloc")]
+    pub(crate) fn to_state(&self, output_bits: u16) -> HashState {
+        HashState {
+            word_bits: 64,
+            output_bits,
+            h: self.h,
+            length: self.length,
+            buffer: self.buffer[..self.filled as usize + (self.partial > 0) as usize].to_vec(),
+        }
+    }
+
+    /// Restore an exported state, checking that it belongs to an algorithm with this output size
+    #[cfg(feature = "alloc")]
+    pub(crate) fn from_state(state: &HashState, output_bits: u16) -> Result<Self, InvalidState> {
+        if state.word_bits != 64 || state.output_bits != output_bits {
+            return Err(InvalidState::WrongAlgorithm);
+        }
+
+        // The buffer holds whatever is left of the message after the last full block, with the
+        // unused bits of a partial last byte cleared
+        let bits = (state.length % 1024) as usize;
+        let partial = (bits % 8) as u8;
+        if state.buffer.len() != bits.div_ceil(8)
+            || (partial > 0 && state.buffer[bits / 8] & (0xff >> partial) != 0)
+        {
+            return Err(InvalidState::InvalidBuffer);
+        }
+
+        let mut engine = Self::new(state.h);
+        engine.buffer[..state.buffer.len()].copy_from_slice(&state.buffer);
+        engine.filled = (bits / 8) as u8;
+        engine.partial = partial;
+        engine.length = state.length;
+        Ok(engine)
+    }
+
+    /// Pad the message and write the first `out.len()` bytes of the final hash value to `out`
+    ///
+    /// Never fails: the length was checked by every update, and the padding isn't counted in it.
+    pub(crate) fn finalize_into(mut self, out: &mut [u8]) {
+        let (padding, len) = self.padding();
+        self.absorb(&padding[..len]);
+        self.output(out);
+    }
+
+    /// [`Self::update`] for constant evaluation
+    ///
+    /// The message never has a partial byte, since the constant hashers only take whole bytes.
+    pub(crate) const fn update_const(&mut self, data: &[u8]) {
+        let bits = match (data.len() as u128).checked_mul(8) {
+            Some(bits) => self.length.checked_add(bits),
+            None => None,
+        };
+        match bits {
+            Some(bits) => self.length = bits,
+            None => panic!("message is too long for the hash algorithm"),
+        }
+        self.absorb_const(data);
+    }
+
+    /// [`Self::absorb`] for constant evaluation, on whole bytes only
+    const fn absorb_const(&mut self, data: &[u8]) {
+        let mut i = 0;
+        while i < data.len() {
+            self.buffer[self.filled as usize] = data[i];
+            self.filled += 1;
+            if self.filled == 128 {
+                compress_block(&mut self.h, &self.buffer);
+                self.filled = 0;
+            }
+            i += 1;
+        }
+    }
+
+    /// [`Self::finalize_into`] for constant evaluation
+    pub(crate) const fn finalize_const(mut self, out: &mut [u8]) {
+        let (padding, len) = self.padding();
+        self.absorb_const(padding.split_at(len).0);
+        self.output(out);
+    }
+
+    /// The padding that completes the message: a one bit, zeros and the length in bits
+    ///
+    /// Returns the padding and its length, the first `len` bytes fill the buffer up to the end
+    /// of a block, with a second block when there is no room for the length in this one. When
+    /// the message ends with a partial byte, the one bit goes right after its last bit, and the
+    /// byte is taken out of the buffer to start the padding.
+    const fn padding(&mut self) -> ([u8; 256], usize) {
+        let mut padding = [0; 256];
+        padding[0] = 0b10000000 >> self.partial;
+        if self.partial > 0 {
+            padding[0] |= self.buffer[self.filled as usize];
+            self.partial = 0;
+        }
+        let len = if self.filled < 128 - 16 {
+            128 - self.filled as usize
+        } else {
+            256 - self.filled as usize
+        };
+        let length = self.length.to_be_bytes();
+        let mut i = 0;
+        while i < 16 {
+            padding[len - 16 + i] = length[i];
+            i += 1;
+        }
+        (padding, len)
+    }
+
+    /// Write the first `out.len()` bytes of the hash value, in big endian, to `out`
+    const fn output(&self, out: &mut [u8]) {
+        let mut i = 0;
+        while i < out.len() {
+            out[i] = (self.h[i / 8] >> (56 - 8 * (i % 8))) as u8;
+            i += 1;
+        }
+    }
+}
+
+/// Update the hash value (state) using each block of the message in turn
+fn compress(state: &mut [u64; 8], blocks: &[[u8; 128]]) {
+    blocks.iter().for_each(|block| compress_block(state, block));
+}
+
+/// Update the hash value using one block of the message
+///
+/// Only uses `while` loops, so the constant hashing functions share it.
+const fn compress_block(state: &mut [u64; 8], block: &[u8; 128]) {
+    // Create an 80-entry message schedule array w[0..79] of 64-bit words
+    let mut w = [0u64; 80];
+    // The initial values in w[0..79] don't matter, so many implementations zero them here
+    // Copy chunk into first 16 words w[0..15] of the message schedule array
+    let mut i = 0;
+    while i < 16 {
+        let (buf, _) = block.split_at(8 * i).1.split_first_chunk::<8>().unwrap();
+        w[i] = u64::from_be_bytes(*buf);
+        i += 1;
+    }
+
+    // Extend the first 16 words into the remaining 64 words w[16..79] of the message schedule array:
+    while i < 80 {
+        let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+        let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+        i += 1;
+    }
+
+    // Initialize working variables to current hash value:
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+
+    // Compression function main loop:
+    let mut i = 0;
+    while i < 80 {
+        // Perform linear manipulation to shuffle the data further
+        let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+        let ch = (e & f) ^ ((!e) & g);
+        let temp1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+
+        // Assign results to temp variables
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+        i += 1;
+    }
+
+    // Add the compressed chunk to the current hash value:
+    state[0] = state[0].wrapping_add(a);
+    state[1] = state[1].wrapping_add(b);
+    state[2] = state[2].wrapping_add(c);
+    state[3] = state[3].wrapping_add(d);
+    state[4] = state[4].wrapping_add(e);
+    state[5] = state[5].wrapping_add(f);
+    state[6] = state[6].wrapping_add(g);
+    state[7] = state[7].wrapping_add(h);
+}
@@ -0,0 +1,173 @@
+use crate::hmac::Hmac;
+use digest::core_api::BlockSizeUser;
+use digest::{Digest, InvalidLength, Mac, OutputSizeUser};
+
+/// HKDF (RFC 5869) over one of the SHA2 hashers, eg. `Hkdf<Sha256>`
+///
+/// Holds the HMAC keyed with the pseudorandom key, so any number of keys can be expanded from it.
+#[derive(Clone)]
+pub struct Hkdf<D> {
+    prk: Hmac<D>,
+}
+
+impl<D: Digest + BlockSizeUser + Clone> Hkdf<D> {
+    /// The extract step: derive a pseudorandom key from the input keying material
+    ///
+    /// Without a salt, a string of zeros the length of the hash is used. Returns the
+    /// pseudorandom key along with the HKDF ready to expand it.
+    pub fn extract(salt: Option<&[u8]>, ikm: &[u8]) -> (digest::Output<D>, Self) {
+        let zeros = digest::Output::<D>::default();
+        let mut mac = Hmac::<D>::new_from_slice(salt.unwrap_or(&zeros)).unwrap();
+        mac.update(ikm);
+        let prk = mac.finalize().into_bytes();
+        let hkdf = Self::from_prk(&prk).unwrap();
+        (prk, hkdf)
+    }
+
+    /// Skip the extract step, using a pseudorandom key that is already uniformly random
+    ///
+    /// Fails if the key is shorter than the hash
+    pub fn from_prk(prk: &[u8]) -> Result<Self, InvalidLength> {
+        if prk.len() < <D as OutputSizeUser>::output_size() {
+            return Err(InvalidLength);
+        }
+        Ok(Self {
+            prk: Hmac::new_from_slice(prk)?,
+        })
+    }
+
+    /// The expand step: fill `okm` with output keying material bound to `info`
+    ///
+    /// Fails if more than 255 hashes worth of output is requested
+    pub fn expand(&self, info: &[u8], okm: &mut [u8]) -> Result<(), InvalidLength> {
+        let hash_len = <D as OutputSizeUser>::output_size();
+        if okm.len() > 255 * hash_len {
+            return Err(InvalidLength);
+        }
+
+        // T(i) = HMAC(PRK, T(i - 1) | info | i), with T(0) empty
+        let mut previous: Option<digest::Output<D>> = None;
+        for (i, chunk) in okm.chunks_mut(hash_len).enumerate() {
+            let mut mac = self.prk.clone();
+            if let Some(previous) = &previous {
+                mac.update(previous);
+            }
+            mac.update(info);
+            mac.update(&[i as u8 + 1]);
+            let t = mac.finalize().into_bytes();
+            chunk.copy_from_slice(&t[..chunk.len()]);
+            previous = Some(t);
+        }
+        Ok(())
+    }
+}
+
+/// Tests for our HKDF implementation
+#[cfg(test)]
+mod tests {
+    use super::Hkdf;
+    use crate::test_util::hex;
+    use crate::{Sha256, Sha384};
+
+    // Extract and expand, and check both the pseudorandom key and the output
+    macro_rules! hkdf_test {
+        ($hash:ty, $salt:expr, $ikm:expr, $info:expr, $prk:literal, $okm:literal) => {
+            let (prk, hkdf) = Hkdf::<$hash>::extract($salt, &$ikm);
+            assert_eq!(hex($prk), prk[..], "Wrong pseudorandom key");
+            let expected = hex($okm);
+            let mut okm = vec![0u8; expected.len()];
+            hkdf.expand(&$info, &mut okm).unwrap();
+            assert_eq!(expected, okm, "Wrong output keying material");
+        };
+    }
+
+    // The SHA256 test cases from RFC 5869
+    #[test]
+    fn rfc5869() {
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        hkdf_test!(
+            Sha256,
+            Some(&salt),
+            [0x0b; 22],
+            info,
+            "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5",
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf\
+             34007208d5b887185865"
+        );
+
+        let salt: Vec<u8> = (0x60..=0xaf).collect();
+        let ikm: Vec<u8> = (0x00..=0x4f).collect();
+        let info: Vec<u8> = (0xb0..=0xff).collect();
+        hkdf_test!(
+            Sha256,
+            Some(&salt),
+            ikm,
+            info,
+            "06a6b88c5853361a06104c9ceb35b45cef760014904671014a193f40c15fc244",
+            "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c\
+             59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71\
+             cc30c58179ec3e87c14c01d5c1f3434f1d87"
+        );
+
+        hkdf_test!(
+            Sha256,
+            None,
+            [0x0b; 22],
+            [],
+            "19ef24a32c717b167f33a91d6f648bdf96596776afdb6377ac434c1c293ccb04",
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d\
+             9d201395faa4b61a96c8"
+        );
+    }
+
+    // RFC 5869 has no SHA384 test cases, so these were computed with an independent implementation
+    #[test]
+    fn sha384() {
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        hkdf_test!(
+            Sha384,
+            Some(&salt),
+            [0x0b; 22],
+            info,
+            "704b39990779ce1dc548052c7dc39f303570dd13fb39f7acc564680bef80e8de\
+             c70ee9a7e1f3e293ef68eceb072a5ade",
+            "9b5097a86038b805309076a44b3a9f38063e25b516dcbf369f394cfab43685f7\
+             48b6457763e4f0204fc5"
+        );
+
+        hkdf_test!(
+            Sha384,
+            None,
+            [0x0b; 22],
+            [],
+            "10e40cf072a4c5626e43dd22c1cf727d4bb140975c9ad0cbc8e45b40068f8f0b\
+             a57cdb598af9dfa6963a96899af047e5",
+            "c8c96e710f89b0d7990bca68bcdec8cf854062e54c73a7abc743fade9b242daa\
+             cc1cea5670415b52849c97c4e787c1f2130fdaaed2efee2fd43ab373b705464f\
+             fbc36e70ca2a61a5ebf44d1bf8217df59cbe9dcf94a5a4546503e7f9c02e97b0\
+             fac5cb8f"
+        );
+    }
+
+    // Too much output or too short pseudorandom keys are rejected
+    #[test]
+    fn invalid_lengths() {
+        let (prk, hkdf) = Hkdf::<Sha256>::extract(None, b"input");
+        let mut okm = vec![0u8; 255 * 32];
+        assert!(hkdf.expand(b"", &mut okm).is_ok());
+        okm.push(0);
+        assert!(hkdf.expand(b"", &mut okm).is_err());
+
+        assert!(Hkdf::<Sha256>::from_prk(&prk[..31]).is_err());
+        let mut from_prk = [0u8; 42];
+        Hkdf::<Sha256>::from_prk(&prk)
+            .unwrap()
+            .expand(b"info", &mut from_prk)
+            .unwrap();
+        let mut extracted = [0u8; 42];
+        hkdf.expand(b"info", &mut extracted).unwrap();
+        assert_eq!(from_prk, extracted);
+    }
+}
@@ -0,0 +1,237 @@
+use digest::core_api::BlockSizeUser;
+use digest::crypto_common::KeySizeUser;
+use digest::{
+    Digest, FixedOutput, FixedOutputReset, InvalidLength, Key, KeyInit, MacMarker, OutputSizeUser,
+    Reset, Update,
+};
+
+/// Inner padding, xored with every byte of the key
+const IPAD: u8 = 0x36;
+/// Outer padding, xored with every byte of the key
+const OPAD: u8 = 0x5c;
+
+/// HMAC (RFC 2104) over one of the SHA2 hashers, eg. `Hmac<Sha256>`
+///
+/// Implements the [`digest::Mac`] trait, so tags should be checked with `verify_slice` and
+/// friends, which compare them in constant time.
+#[derive(Clone)]
+pub struct Hmac<D> {
+    inner: D,
+    inner_start: D,
+    outer_start: D,
+}
+
+/// Any key length is accepted: keys longer than the block size are hashed first
+impl<D: Digest + BlockSizeUser + Clone> KeyInit for Hmac<D> {
+    fn new(key: &Key<Self>) -> Self {
+        Self::new_from_slice(key).unwrap()
+    }
+
+    fn new_from_slice(key: &[u8]) -> Result<Self, InvalidLength> {
+        // The key is padded with zeros up to the block size
+        let mut block = digest::core_api::Block::<D>::default();
+        if key.len() > block.len() {
+            let hash = D::digest(key);
+            block[..hash.len()].copy_from_slice(&hash);
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        // Absorb the padded keys now, so they don't have to be hashed again after a reset
+        block.iter_mut().for_each(|b| *b ^= IPAD);
+        let inner_start = D::new_with_prefix(&block);
+        block.iter_mut().for_each(|b| *b ^= IPAD ^ OPAD);
+        let outer_start = D::new_with_prefix(&block);
+
+        Ok(Self {
+            inner: inner_start.clone(),
+            inner_start,
+            outer_start,
+        })
+    }
+}
+
+/// The key is the size of a block
+impl<D: BlockSizeUser> KeySizeUser for Hmac<D> {
+    type KeySize = D::BlockSize;
+}
+
+/// The tag is the size of the hash
+impl<D: OutputSizeUser> OutputSizeUser for Hmac<D> {
+    type OutputSize = D::OutputSize;
+}
+
+/// Adds the message to the inner hash
+impl<D: Digest> Update for Hmac<D> {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(&mut self.inner, data);
+    }
+}
+
+/// Computes the tag, by hashing the inner hash with the outer key
+impl<D: Digest> FixedOutput for Hmac<D> {
+    fn finalize_into(self, out: &mut digest::Output<Self>) {
+        self.outer_start
+            .chain_update(self.inner.finalize())
+            .finalize_into(out);
+    }
+}
+
+impl<D> MacMarker for Hmac<D> {}
+
+/// Reset to the state right after the key was set
+impl<D: Clone> Reset for Hmac<D> {
+    fn reset(&mut self) {
+        self.inner = self.inner_start.clone();
+    }
+}
+
+/// Reset our algorithm's output
+impl<D: Digest + Clone> FixedOutputReset for Hmac<D> {
+    fn finalize_into_reset(&mut self, out: &mut digest::Output<Self>) {
+        let inner = core::mem::replace(&mut self.inner, self.inner_start.clone());
+        self.outer_start
+            .clone()
+            .chain_update(inner.finalize())
+            .finalize_into(out);
+    }
+}
+
+/// Tests for our HMAC implementation
+#[cfg(test)]
+mod tests {
+    use super::Hmac;
+    use crate::test_util::hex;
+    use crate::{Sha224, Sha256, Sha384, Sha512};
+    use digest::Mac;
+
+    // The keys and messages of the RFC 4231 test cases
+    fn rfc4231_case(case: usize) -> (Vec<u8>, Vec<u8>) {
+        let long_key = vec![0xaa; 131];
+        match case {
+            1 => (vec![0x0b; 20], b"Hi There".to_vec()),
+            2 => (b"Jefe".to_vec(), b"what do ya want for nothing?".to_vec()),
+            3 => (vec![0xaa; 20], vec![0xdd; 50]),
+            4 => ((1..=25).collect(), vec![0xcd; 50]),
+            5 => (vec![0x0c; 20], b"Test With Truncation".to_vec()),
+            6 => (
+                long_key,
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+            ),
+            7 => (
+                long_key,
+                b"This is a test using a larger than block-size key and a larger than block-size \
+                data. The key needs to be hashed before being used by the HMAC algorithm."
+                    .to_vec(),
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    // Compute the tag for every RFC 4231 test case, and check it against the expected tags.
+    // Test case 5 only checks the first 128 bits of the tag.
+    macro_rules! rfc4231_test {
+        ($name:ident, $hash:ty, [$($expected:literal),* $(,)?]) => {
+            #[test]
+            fn $name() {
+                for (i, expected) in [$($expected),*].into_iter().enumerate() {
+                    let (key, data) = rfc4231_case(i + 1);
+                    let expected = hex(expected);
+                    let mut mac = Hmac::<$hash>::new_from_slice(&key).unwrap();
+                    mac.update(&data);
+                    if i + 1 == 5 {
+                        mac.verify_truncated_left(&expected[..16])
+                    } else {
+                        mac.verify_slice(&expected)
+                    }
+                    .unwrap_or_else(|_| panic!("Failed RFC 4231 test case {}", i + 1));
+                }
+            }
+        };
+    }
+
+    rfc4231_test!(
+        rfc4231_sha224,
+        Sha224,
+        [
+            "896fb1128abbdf196832107cd49df33f47b4b1169912ba4f53684b22",
+            "a30e01098bc6dbbf45690f3a7e9e6d0f8bbea2a39e6148008fd05e44",
+            "7fb3cb3588c6c1f6ffa9694d7d6ad2649365b0c1f65d69d1ec8333ea",
+            "6c11506874013cac6a2abc1bb382627cec6a90d86efc012de7afec5a",
+            "0e2aea68a90c8d37c988bcdb9fca6fa8",
+            "95e9a0db962095adaebe9b2d6f0dbce2d499f112f2d2b7273fa6870e",
+            "3a854166ac5d9f023f54d517d0b39dbd946770db9c2b95c9f6f565d1",
+        ]
+    );
+
+    rfc4231_test!(
+        rfc4231_sha256,
+        Sha256,
+        [
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            "a3b6167473100ee06e0c796c2955552b",
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+        ]
+    );
+
+    rfc4231_test!(
+        rfc4231_sha384,
+        Sha384,
+        [
+            "afd03944d84895626b0825f4ab46907f15f9dadbe4101ec682aa034c7cebc59c\
+             faea9ea9076ede7f4af152e8b2fa9cb6",
+            "af45d2e376484031617f78d2b58a6b1b9c7ef464f5a01b47e42ec3736322445e\
+             8e2240ca5e69e2c78b3239ecfab21649",
+            "88062608d3e6ad8a0aa2ace014c8a86f0aa635d947ac9febe83ef4e55966144b\
+             2a5ab39dc13814b94e3ab6e101a34f27",
+            "3e8a69b7783c25851933ab6290af6ca77a9981480850009cc5577c6e1f573b4e\
+             6801dd23c4a7d679ccf8a386c674cffb",
+            "3abf34c3503b2a23a46efc619baef897",
+            "4ece084485813e9088d2c63a041bc5b44f9ef1012a2b588f3cd11f05033ac4c6\
+             0c2ef6ab4030fe8296248df163f44952",
+            "6617178e941f020d351e2f254e8fd32c602420feb0b8fb9adccebb82461e99c5\
+             a678cc31e799176d3860e6110c46523e",
+        ]
+    );
+
+    rfc4231_test!(
+        rfc4231_sha512,
+        Sha512,
+        [
+            "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cde\
+             daa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+             9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            "fa73b0089d56a284efb0f0756c890be9b1b5dbdd8ee81a3655f83e33b2279d39\
+             bf3e848279a722c806b485a47e67c807b946a337bee8942674278859e13292fb",
+            "b0ba465637458c6990e5a8c5f61d4af7e576d97ff94b872de76f8050361ee3db\
+             a91ca5c11aa25eb4d679275cc5788063a5f19741120c4f2de2adebeb10a298dd",
+            "415fad6271580a531d4179bc891d87a6",
+            "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f352\
+             6b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+            "e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944\
+             b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58",
+        ]
+    );
+
+    // Wrong tags are rejected, and resetting gives the same tag again
+    #[test]
+    fn verify_and_reset() {
+        let (key, data) = rfc4231_case(2);
+        let mut mac = Hmac::<Sha256>::new_from_slice(&key).unwrap();
+        mac.update(&data);
+        let tag = mac.clone().finalize().into_bytes();
+        let mut wrong = tag;
+        wrong[31] ^= 1;
+        assert!(mac.clone().verify_slice(&wrong).is_err());
+        assert!(mac.clone().verify_slice(&tag[..31]).is_err());
+
+        assert_eq!(mac.finalize_reset().into_bytes(), tag);
+        mac.update(&data);
+        assert!(mac.verify_slice(&tag).is_ok());
+    }
+}
@@ -0,0 +1,376 @@
+use digest::{Digest, Output};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+/// Async adapters for tokio readers and writers
+#[cfg(feature = "tokio")]
+mod tokio;
+#[cfg(feature = "tokio")]
+pub use self::tokio::{hash_async_reader, AsyncHashingReader, AsyncHashingWriter};
+
+/// Size of the reads done by [`hash_reader`]
+const READ_SIZE: usize = 64 * 1024;
+
+/// Hash everything a reader returns until the end of the stream
+///
+/// Returns the hash and the length of the stream in bytes. The reads are large, so most blocks
+/// are compressed without being copied into the hasher's buffer.
+pub fn hash_reader<D: Digest>(mut reader: impl Read) -> io::Result<(Output<D>, u64)> {
+    let mut hasher = D::new();
+    let mut length = 0;
+    let mut buffer = vec![0u8; READ_SIZE];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok((hasher.finalize(), length)),
+            Ok(n) => {
+                hasher.update(&buffer[..n]);
+                length += n as u64;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Hash the contents of a file
+pub fn hash_file<D: Digest>(path: &Path) -> io::Result<Output<D>> {
+    Ok(hash_reader::<D>(File::open(path)?)?.0)
+}
+
+/// Hash files on as many threads as the system can run in parallel
+///
+/// Returns the hash of each file, or the error reading it, in the order of `paths`.
+///
+/// ```
+/// use sha_crypto::{io::hash_files_parallel, Sha256};
+///
+/// let results = hash_files_parallel::<Sha256>(&["Cargo.toml", "missing"]);
+/// assert!(results[0].is_ok());
+/// assert!(results[1].is_err());
+/// ```
+pub fn hash_files_parallel<D: Digest>(
+    paths: &[impl AsRef<Path> + Sync],
+) -> Vec<io::Result<Output<D>>> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut results = Vec::with_capacity(paths.len());
+    hash_files_parallel_with::<D>(paths, threads, |_, result| results.push(result));
+    results
+}
+
+/// Hash files on `threads` worker threads, passing each result to `on_result` in input order
+///
+/// `on_result` gets the index of the path along with its hash or error, on the calling thread.
+/// Results that finish early are held back until all the files before them are done, so output
+/// can be written as soon as possible without reordering it.
+pub fn hash_files_parallel_with<D: Digest>(
+    paths: &[impl AsRef<Path> + Sync],
+    threads: usize,
+    on_result: impl FnMut(usize, io::Result<Output<D>>),
+) {
+    parallel_ordered(paths, threads, hash_file::<D>, on_result)
+}
+
+/// Run `hash` on each path on `threads` worker threads, passing the results to `on_result` in
+/// input order
+///
+/// This is [`hash_files_parallel_with`] with a custom function to hash each file, for example
+/// to choose the algorithm at runtime or to memory map the files.
+pub fn parallel_ordered<P: AsRef<Path> + Sync, T: Send>(
+    paths: &[P],
+    threads: usize,
+    hash: impl Fn(&Path) -> T + Sync,
+    mut on_result: impl FnMut(usize, T),
+) {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, paths.len().max(1)) {
+            let sender = sender.clone();
+            let (next, hash) = (&next, &hash);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    return;
+                };
+                if sender.send((index, hash(path.as_ref()))).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(sender);
+
+        // Hold back the results that arrive before the ones preceding them
+        let mut pending = std::collections::BTreeMap::new();
+        let mut expected = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&expected) {
+                on_result(expected, result);
+                expected += 1;
+            }
+        }
+    });
+}
+
+/// Hash the contents of a file by memory mapping it
+///
+/// The whole file is passed to the hasher in one slice, so there is no read for each buffer.
+/// Pipes, special files, empty files and files that can't be mapped are read with
+/// [`hash_reader`] instead.
+///
+/// The file must not be modified while it is hashed: the hash would be meaningless, and a file
+/// truncated by another process can make the mapping fault.
+#[cfg(feature = "mmap")]
+pub fn hash_file_mmap<D: Digest>(path: &Path) -> io::Result<Output<D>> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() || metadata.len() == 0 {
+        return Ok(hash_reader::<D>(file)?.0);
+    }
+
+    // Safety: the mapping is only read while hashing, see above for concurrent modifications
+    let map = match unsafe { memmap2::Mmap::map(&file) } {
+        Ok(map) => map,
+        Err(_) => return Ok(hash_reader::<D>(file)?.0),
+    };
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+    Ok(D::digest(&map[..]))
+}
+
+/// A reader that hashes everything read through it
+///
+/// ```
+/// use sha_crypto::{io::HashingReader, Sha256};
+/// use std::io::Read;
+///
+/// let mut reader = HashingReader::<_, Sha256>::new(&b"hello world"[..]);
+/// let mut contents = String::new();
+/// reader.read_to_string(&mut contents).unwrap();
+/// let (hash, length) = reader.finish();
+/// assert_eq!(hash[..4], [0xb9, 0x4d, 0x27, 0xb9]);
+/// assert_eq!(length, 11);
+/// ```
+#[derive(Clone)]
+pub struct HashingReader<R, D> {
+    inner: R,
+    hasher: D,
+    length: u64,
+}
+
+impl<R, D: Digest> HashingReader<R, D> {
+    /// Wrap a reader, starting a new hash
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: D::new(),
+            length: 0,
+        }
+    }
+
+    /// The wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// The wrapped reader. Reading from it directly skips the hash
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// The hash of everything read so far, and its length in bytes
+    pub fn finish(self) -> (Output<D>, u64) {
+        (self.hasher.finalize(), self.length)
+    }
+}
+
+/// Hashes the bytes the inner reader returns
+impl<R: Read, D: Digest> Read for HashingReader<R, D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.length += n as u64;
+        Ok(n)
+    }
+}
+
+/// A writer that hashes everything written through it
+///
+/// ```
+/// use sha_crypto::{io::HashingWriter, Sha256};
+/// use std::io::Write;
+///
+/// let mut writer = HashingWriter::<_, Sha256>::new(Vec::new());
+/// writer.write_all(b"hello world").unwrap();
+/// assert_eq!(writer.get_ref(), b"hello world");
+/// let (hash, length) = writer.finish();
+/// assert_eq!(hash[..4], [0xb9, 0x4d, 0x27, 0xb9]);
+/// assert_eq!(length, 11);
+/// ```
+#[derive(Clone)]
+pub struct HashingWriter<W, D> {
+    inner: W,
+    hasher: D,
+    length: u64,
+}
+
+impl<W, D: Digest> HashingWriter<W, D> {
+    /// Wrap a writer, starting a new hash
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: D::new(),
+            length: 0,
+        }
+    }
+
+    /// The wrapped writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// The wrapped writer. Writing to it directly skips the hash
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// The hash of everything written so far, and its length in bytes
+    ///
+    /// The inner writer isn't flushed, call [`Write::flush`] first if it buffers.
+    pub fn finish(self) -> (Output<D>, u64) {
+        (self.hasher.finalize(), self.length)
+    }
+}
+
+/// Hashes the bytes the inner writer accepts
+impl<W: Write, D: Digest> Write for HashingWriter<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.length += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Tests for the reader and writer adapters
+#[cfg(test)]
+mod tests {
+    use super::{HashingReader, HashingWriter};
+    use crate::{Digest, Sha256, Sha512};
+    use std::io::{self, Read, Write};
+
+    // A writer that accepts at most 7 bytes per call
+    struct Trickle(Vec<u8>);
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(7);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // The hashers can be the destination of io::copy
+    #[test]
+    fn copy_into_hasher() {
+        let data: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
+        let mut hasher = Sha512::new();
+        let copied = io::copy(&mut &data[..], &mut hasher).unwrap();
+        assert_eq!(copied, data.len() as u64);
+        assert_eq!(hasher.finalize(), sha2::Sha512::digest(&data));
+
+        let mut hasher = crate::Sha512t::new(100).unwrap();
+        io::copy(&mut &data[..], &mut hasher).unwrap();
+        let mut my_res = [0u8; 13];
+        digest::VariableOutput::finalize_variable(hasher, &mut my_res).unwrap();
+        let mut expected = crate::Sha512t::new(100).unwrap();
+        digest::Update::update(&mut expected, &data);
+        let mut ex_res = [0u8; 13];
+        digest::VariableOutput::finalize_variable(expected, &mut ex_res).unwrap();
+        assert_eq!(ex_res, my_res);
+    }
+
+    // Files hashed in parallel come back in order, with the errors in place
+    #[test]
+    fn parallel() {
+        let dir = crate::test_util::ScratchDir::new("parallel");
+        let mut paths = Vec::new();
+        let mut expected = Vec::new();
+        for i in 0..50usize {
+            let data: Vec<u8> = (0..i * 997).map(|j| (i + j) as u8).collect();
+            let path = dir.join(i.to_string());
+            std::fs::write(&path, &data).unwrap();
+            paths.push(path);
+            expected.push(Some(sha2::Sha256::digest(&data)));
+        }
+        paths.insert(7, dir.join("missing"));
+        expected.insert(7, None);
+
+        let results = super::hash_files_parallel::<Sha256>(&paths);
+        let results: Vec<_> = results.into_iter().map(|r| r.ok()).collect();
+        assert_eq!(results, expected);
+
+        let mut indices = Vec::new();
+        super::hash_files_parallel_with::<Sha256>(&paths, 4, |i, result| {
+            assert_eq!(result.ok(), expected[i]);
+            indices.push(i);
+        });
+        assert_eq!(indices, (0..paths.len()).collect::<Vec<_>>());
+        super::hash_files_parallel_with::<Sha256>(&[] as &[&str], 4, |_, _| unreachable!());
+    }
+
+    // Memory mapped hashing, with the fallbacks for files that can't be mapped
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap() {
+        let dir = crate::test_util::ScratchDir::new("mmap");
+        let data: Vec<u8> = (0..1_000_003).map(|i| (i * 13) as u8).collect();
+        std::fs::write(dir.join("data"), &data).unwrap();
+        std::fs::write(dir.join("empty"), b"").unwrap();
+
+        let hash = super::hash_file_mmap::<Sha256>(&dir.join("data")).unwrap();
+        assert_eq!(hash, sha2::Sha256::digest(&data));
+        let hash = super::hash_file_mmap::<Sha256>(&dir.join("empty")).unwrap();
+        assert_eq!(hash, sha2::Sha256::digest(b""));
+        assert!(super::hash_file_mmap::<Sha256>(&dir.join("missing")).is_err());
+        #[cfg(unix)]
+        {
+            let hash = super::hash_file_mmap::<Sha256>("/dev/null".as_ref()).unwrap();
+            assert_eq!(hash, sha2::Sha256::digest(b""));
+        }
+    }
+
+    // Everything read or written passes through unchanged, and is hashed exactly once
+    #[test]
+    fn pass_through() {
+        let data: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+        let expected = sha2::Sha256::digest(&data);
+
+        assert_eq!(
+            super::hash_reader::<Sha256>(&data[..]).unwrap(),
+            (expected, 1000)
+        );
+
+        let mut reader = HashingReader::<_, Sha256>::new(&data[..]);
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+        assert_eq!(reader.finish(), (expected, 1000));
+
+        let mut writer = HashingWriter::<_, Sha256>::new(Trickle(Vec::new()));
+        writer.write_all(&data).unwrap();
+        assert_eq!(writer.get_ref().0, data);
+        assert_eq!(writer.finish(), (expected, 1000));
+    }
+}
@@ -0,0 +1,195 @@
+use digest::{Digest, Output};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+/// The async version of [`HashingReader`](super::HashingReader), for tokio readers
+///
+/// The reader must be `Unpin`, wrap it in `Box::pin` if it isn't.
+pub struct AsyncHashingReader<R, D> {
+    inner: R,
+    hasher: D,
+    length: u64,
+}
+
+impl<R, D: Digest> AsyncHashingReader<R, D> {
+    /// Wrap a reader, starting a new hash
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: D::new(),
+            length: 0,
+        }
+    }
+
+    /// The wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// The wrapped reader. Reading from it directly skips the hash
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// The hash of everything read so far, and its length in bytes
+    pub fn finish(self) -> (Output<D>, u64) {
+        (self.hasher.finalize(), self.length)
+    }
+}
+
+/// Hashes the bytes the inner reader returns
+impl<R: AsyncRead + Unpin, D: Digest + Unpin> AsyncRead for AsyncHashingReader<R, D> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let start = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let read = &buf.filled()[start..];
+            this.hasher.update(read);
+            this.length += read.len() as u64;
+        }
+        result
+    }
+}
+
+/// The async version of [`HashingWriter`](super::HashingWriter), for tokio writers
+///
+/// The writer must be `Unpin`, wrap it in `Box::pin` if it isn't.
+pub struct AsyncHashingWriter<W, D> {
+    inner: W,
+    hasher: D,
+    length: u64,
+}
+
+impl<W, D: Digest> AsyncHashingWriter<W, D> {
+    /// Wrap a writer, starting a new hash
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: D::new(),
+            length: 0,
+        }
+    }
+
+    /// The wrapped writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// The wrapped writer. Writing to it directly skips the hash
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// The hash of everything written so far, and its length in bytes
+    ///
+    /// The inner writer isn't flushed, flush or shut it down first if it buffers.
+    pub fn finish(self) -> (Output<D>, u64) {
+        (self.hasher.finalize(), self.length)
+    }
+}
+
+/// Hashes the bytes the inner writer accepts
+impl<W: AsyncWrite + Unpin, D: Digest + Unpin> AsyncWrite for AsyncHashingWriter<W, D> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            this.hasher.update(&buf[..n]);
+            this.length += n as u64;
+        }
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Hash everything a tokio reader returns until the end of the stream
+///
+/// Returns the hash and the length of the stream in bytes. The stream is hashed in chunks, so
+/// it is never held in memory as a whole.
+pub async fn hash_async_reader<D: Digest>(
+    mut reader: impl AsyncRead + Unpin,
+) -> io::Result<(Output<D>, u64)> {
+    let mut hasher = D::new();
+    let mut length = 0;
+    let mut buffer = vec![0u8; super::READ_SIZE];
+    loop {
+        let n = reader.read(&mut buffer).await?;
+        if n == 0 {
+            return Ok((hasher.finalize(), length));
+        }
+        hasher.update(&buffer[..n]);
+        length += n as u64;
+    }
+}
+
+/// Tests for the async adapters
+#[cfg(test)]
+mod tests {
+    use super::{hash_async_reader, AsyncHashingReader, AsyncHashingWriter};
+    use crate::{Digest, Sha256, Sha384};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Everything read or written passes through unchanged, and is hashed exactly once
+    #[tokio::test]
+    async fn pass_through() {
+        let data: Vec<u8> = (0..200_000).map(|i| (i * 7) as u8).collect();
+        let expected = sha2::Sha256::digest(&data);
+
+        let mut reader = AsyncHashingReader::<_, Sha256>::new(&data[..]);
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).await.unwrap();
+        assert_eq!(read, data);
+        assert_eq!(reader.finish(), (expected, 200_000));
+
+        let mut writer = AsyncHashingWriter::<_, Sha256>::new(Vec::new());
+        writer.write_all(&data).await.unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(writer.get_ref(), &data);
+        assert_eq!(writer.finish(), (expected, 200_000));
+    }
+
+    // Streaming a reader into a writer hashes it on the way through
+    #[tokio::test]
+    async fn stream_to_writer() {
+        let data: Vec<u8> = (0..100_000).map(|i| (i * 3) as u8).collect();
+        let (mut client, server) = tokio::io::duplex(1000);
+        let send = async {
+            client.write_all(&data).await.unwrap();
+            client.shutdown().await.unwrap();
+        };
+        let mut reader = AsyncHashingReader::<_, Sha384>::new(server);
+        let mut written = Vec::new();
+        let receive = tokio::io::copy(&mut reader, &mut written);
+        let ((), copied) = tokio::join!(send, receive);
+        assert_eq!(copied.unwrap(), 100_000);
+        assert_eq!(written, data);
+        assert_eq!(reader.finish().0, sha2::Sha384::digest(&data));
+    }
+
+    // The helper reads to the end of the stream
+    #[tokio::test]
+    async fn helper() {
+        let data: Vec<u8> = (0..100_000).map(|i| i as u8).collect();
+        let (hash, length) = hash_async_reader::<Sha256>(&data[..]).await.unwrap();
+        assert_eq!(hash, sha2::Sha256::digest(&data));
+        assert_eq!(length, 100_000);
+    }
+}
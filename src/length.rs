@@ -0,0 +1,16 @@
+use core::fmt;
+
+/// Error for a message longer than the algorithm can hash
+///
+/// The padding ends with the length of the message in bits, on 64 bits for SHA256 and SHA224 and
+/// on 128 bits for the others, so a message of `2^64` bits or more has no SHA256 hash.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LengthOverflow;
+
+impl fmt::Display for LengthOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("message is too long for the hash algorithm")
+    }
+}
+
+impl core::error::Error for LengthOverflow {}
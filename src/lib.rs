@@ -0,0 +1,132 @@
+//! A simple implementation of the SHA2 family of hash functions.
+//!
+//! Every hasher implements the traits from the [`digest`] crate, so they can be used anywhere the
+//! `sha2` crate's hashers are accepted:
+//!
+//! ```
+//! use sha_crypto::{Digest, Sha256};
+//!
+//! let hash = Sha256::new().chain_update(b"hello world").finalize();
+//! assert_eq!(hash[..4], [0xb9, 0x4d, 0x27, 0xb9]);
+//! ```
+//!
+//! Without the default `std` feature the crate is `no_std`, for firmware and WASM: the hashers,
+//! HMAC, HKDF, PBKDF2 and double SHA256 only need `core`. The `alloc` feature adds the exported
+//! states, Merkle trees and crypt strings, `std` the I/O adapters and checksum manifests, and `cli`
+//! the directory trees of the command-line tool.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub use digest::{self, Digest, Mac};
+
+#[macro_use]
+mod macros;
+
+/// The compression engines shared by the variants
+mod engine256;
+mod engine512;
+
+/// Keyed hashing and key derivation built on the SHA2 algorithms
+pub mod hkdf;
+pub mod hmac;
+pub mod pbkdf2;
+#[cfg(feature = "alloc")]
+pub mod sha_crypt;
+
+/// Hashing data as it is read or written, checksum manifests and directory trees
+#[cfg(feature = "std")]
+pub mod algorithm;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "std")]
+pub mod manifest;
+#[cfg(feature = "cli")]
+pub mod tree;
+
+/// Merkle trees with inclusion and consistency proofs
+#[cfg(feature = "alloc")]
+pub mod merkle;
+
+/// The error for messages too long to hash
+mod length;
+
+/// Exporting and restoring the internal state of the hashers
+#[cfg(feature = "alloc")]
+mod state;
+
+/// The different SHA2 algorithms
+pub mod sha224;
+pub mod sha256;
+pub mod sha384;
+pub mod sha512;
+pub mod sha512t;
+
+/// Double SHA256 and the tagged hashes of Bitcoin
+pub mod sha256d;
+
+/// Hashing in constant evaluation
+pub mod const_hash;
+
+#[cfg(feature = "std")]
+pub use algorithm::Algorithm;
+pub use hkdf::Hkdf;
+pub use hmac::Hmac;
+pub use length::LengthOverflow;
+#[cfg(feature = "alloc")]
+pub use merkle::MerkleTree;
+pub use pbkdf2::pbkdf2_hmac;
+pub use sha224::Sha224;
+pub use sha256::Sha256;
+pub use sha256d::{Sha256d, TaggedHash};
+pub use sha384::Sha384;
+pub use sha512::{Sha512, Sha512_224, Sha512_256};
+pub use sha512t::{Sha512Trunc, Sha512t};
+#[cfg(feature = "alloc")]
+pub use state::{HashState, InvalidState, STATE_VERSION};
+
+/// Helpers shared by the tests
+#[cfg(test)]
+mod test_util {
+    /// Decode a hex string into bytes
+    pub(crate) fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// An empty directory for a test under the temporary directory, removed when dropped
+    #[cfg(feature = "std")]
+    pub(crate) struct ScratchDir(std::path::PathBuf);
+
+    #[cfg(feature = "std")]
+    impl ScratchDir {
+        /// Create the directory, named after the test and the process
+        pub(crate) fn new(test: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("sha-crypto-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl core::ops::Deref for ScratchDir {
+        type Target = std::path::Path;
+
+        fn deref(&self) -> &std::path::Path {
+            &self.0
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+}
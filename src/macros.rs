@@ -0,0 +1,218 @@
+/// Implements the `digest` traits for a thin wrapper around one of the SHA2 engines
+///
+/// The wrapper must be a struct with an `engine` field. `$iv` is the initial hash value the
+/// engine starts from, `$size` is the output size in bytes and `$block` the engine's block size
+/// in bytes (both from `digest::consts`).
+///
+/// Generic wrappers use the second form, which takes the generic parameters in brackets. They
+/// must provide a `with_engine` function to create the wrapper around an engine.
+macro_rules! impl_sha {
+    ($name:ident, $engine:ty, $iv:expr, $size:ty, $block:ty) => {
+        impl $name {
+            /// Create the wrapper around an engine
+            fn with_engine(engine: $engine) -> Self {
+                Self { engine }
+            }
+        }
+
+        impl_sha!([] $name, $engine, $iv, $size, $block);
+    };
+    ([$($generics:tt)*] $name:ty, $engine:ty, $iv:expr, $size:ty, $block:ty) => {
+        impl<$($generics)*> $name {
+            /// Hash `data`, or fail without hashing anything if the message would get longer than
+            /// the algorithm allows
+            ///
+            /// The limit is `2^64 - 1` bits for SHA256 and SHA224, and `2^128 - 1` bits for the
+            /// others. [`Digest::update`](digest::Digest::update) panics instead of failing, and
+            /// finalising never fails, so a hasher never outputs the hash of a truncated length.
+            pub fn try_update(&mut self, data: &[u8]) -> Result<(), $crate::LengthOverflow> {
+                self.engine.try_update(data)
+            }
+
+            /// Hash the first `bit_len` bits of `data`, for messages that aren't a whole number
+            /// of bytes
+            ///
+            /// The bits of each byte are taken from the most significant one, like in the NIST
+            /// test vectors. Data hashed after a partial byte continues right after its last bit.
+            ///
+            /// # Panics
+            ///
+            /// If `data` has less than `bit_len` bits, or if the message gets longer than the
+            /// algorithm allows (see [`Self::try_update`]).
+            pub fn update_bits(&mut self, data: &[u8], bit_len: usize) {
+                self.engine.update_bits(data, bit_len)
+            }
+
+            /// The output size in bits
+            #[cfg(feature = "alloc")]
+            const OUTPUT_BITS: u16 = (<$size as digest::typenum::Unsigned>::USIZE * 8) as u16;
+
+            /// Export the internal state, to resume hashing later with [`Self::from_state`]
+            #[cfg(feature = "alloc")]
+            pub fn state(&self) -> $crate::HashState {
+                self.engine.to_state(Self::OUTPUT_BITS)
+            }
+
+            /// Restore a hasher from an exported state
+            ///
+            /// Fails if the state belongs to another algorithm or isn't consistent
+            #[cfg(feature = "alloc")]
+            pub fn from_state(state: &$crate::HashState) -> Result<Self, $crate::InvalidState> {
+                <$engine>::from_state(state, Self::OUTPUT_BITS).map(Self::with_engine)
+            }
+
+            /// Export the internal state in the versioned byte format of [`HashState`]
+            ///
+            /// [`HashState`]: $crate::HashState
+            #[cfg(feature = "alloc")]
+            pub fn to_state_bytes(&self) -> alloc::vec::Vec<u8> {
+                self.state().encode()
+            }
+
+            /// Restore a hasher from the bytes written by [`Self::to_state_bytes`]
+            #[cfg(feature = "alloc")]
+            pub fn from_state_bytes(bytes: &[u8]) -> Result<Self, $crate::InvalidState> {
+                Self::from_state(&$crate::HashState::from_bytes(bytes)?)
+            }
+        }
+
+        /// Serialised as its [`HashState`](crate::HashState)
+        #[cfg(feature = "serde")]
+        impl<$($generics)*> serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.state().serialize(serializer)
+            }
+        }
+
+        /// Deserialised from its [`HashState`](crate::HashState), which is validated
+        #[cfg(feature = "serde")]
+        impl<'de, $($generics)*> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let state = $crate::HashState::deserialize(deserializer)?;
+                Self::from_state(&state).map_err(serde::de::Error::custom)
+            }
+        }
+
+        /// Updates the struct variables for the hash
+        ///
+        /// Panics if the message gets longer than the algorithm allows, see `try_update`.
+        impl<$($generics)*> digest::Update for $name {
+            fn update(&mut self, data: &[u8]) {
+                self.engine.update(data)
+            }
+        }
+
+        /// Hashes everything written, so the hasher can be the destination of `io::copy`
+        ///
+        /// Writing past the longest message the algorithm allows fails with `InvalidInput`.
+        #[cfg(feature = "std")]
+        impl<$($generics)*> std::io::Write for $name {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.engine
+                    .try_update(buf)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        /// Pad the message and output the (possibly truncated) hash value
+        ///
+        /// Never panics: the updates made sure the length of the message fits in the padding.
+        impl<$($generics)*> digest::FixedOutput for $name {
+            fn finalize_into(self, out: &mut digest::Output<Self>) {
+                self.engine.finalize_into(out)
+            }
+        }
+
+        /// Defines useful functions for our struct
+        impl<$($generics)*> digest::Digest for $name {
+            fn new() -> Self {
+                Self::with_engine(<$engine>::new($iv))
+            }
+
+            // Helper methods to make our hash function simpler and more compact
+            fn update(&mut self, data: impl AsRef<[u8]>) {
+                self.engine.update(data.as_ref())
+            }
+
+            fn finalize_into(self, out: &mut digest::Output<Self>) {
+                digest::FixedOutput::finalize_into(self, out)
+            }
+
+            fn new_with_prefix(data: impl AsRef<[u8]>) -> Self {
+                digest::Digest::chain_update(Self::new(), data)
+            }
+
+            fn chain_update(mut self, data: impl AsRef<[u8]>) -> Self {
+                self.engine.update(data.as_ref());
+                self
+            }
+
+            fn finalize(self) -> digest::Output<Self> {
+                let mut tmp = digest::Output::<Self>::default();
+                digest::FixedOutput::finalize_into(self, &mut tmp);
+                tmp
+            }
+
+            fn output_size() -> usize {
+                <Self as digest::OutputSizeUser>::output_size()
+            }
+
+            fn digest(data: impl AsRef<[u8]>) -> digest::Output<Self> {
+                digest::Digest::finalize(Self::new_with_prefix(data))
+            }
+
+            fn finalize_reset(&mut self) -> digest::Output<Self>
+            where
+                Self: digest::FixedOutputReset,
+            {
+                digest::Digest::finalize(core::mem::replace(self, Self::new()))
+            }
+
+            fn finalize_into_reset(&mut self, out: &mut digest::Output<Self>)
+            where
+                Self: digest::FixedOutputReset,
+            {
+                digest::FixedOutputReset::finalize_into_reset(self, out);
+            }
+
+            fn reset(&mut self)
+            where
+                Self: digest::Reset,
+            {
+                *self = Self::new();
+            }
+        }
+
+        /// Defines the output size
+        impl<$($generics)*> digest::OutputSizeUser for $name {
+            type OutputSize = $size;
+        }
+
+        /// Defines the block size, used by HMAC
+        impl<$($generics)*> digest::core_api::BlockSizeUser for $name {
+            type BlockSize = $block;
+        }
+
+        /// Reset function for our hash algorithm
+        impl<$($generics)*> digest::Reset for $name {
+            fn reset(&mut self) {
+                *self = <Self as digest::Digest>::new();
+            }
+        }
+
+        /// Reset our algorithm's output
+        impl<$($generics)*> digest::FixedOutputReset for $name {
+            fn finalize_into_reset(&mut self, out: &mut digest::Output<Self>) {
+                digest::FixedOutput::finalize_into(
+                    core::mem::replace(self, <Self as digest::Digest>::new()),
+                    out,
+                )
+            }
+        }
+    };
+}
@@ -1,286 +1,539 @@
-use digest::{Digest, FixedOutput, FixedOutputReset, OutputSizeUser, Reset, Update};
-
-/// The different SHA2 algorithms
-mod sha224;
-mod sha384;
-mod sha512;
-
-/// Struct for SHA256
-struct Sha256 {
-    h: [u32; 8],
-    buffer: [u8; 64],
-    filled: u8,
-    length: u64,
+use sha_crypto::digest::core_api::BlockSizeUser;
+use sha_crypto::manifest::{self, Entry};
+use sha_crypto::tree::TreeOptions;
+use sha_crypto::{Algorithm, Digest, Sha224, Sha256, Sha384, Sha512, Sha512_224, Sha512_256};
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+/// The name used in messages
+const NAME: &str = "sha-crypto";
+
+/// Size of the message hashed by the benchmark
+const BENCH_SIZE: usize = 64 << 20;
+
+/// The text printed by `--help`
+const USAGE: &str = "\
+Usage: sha-crypto [OPTION]... [FILE]...
+  or:  sha-crypto [OPTION]... --tree DIR [--tree DIR]...
+Print or check SHA2 checksums, in the format of GNU sha256sum.
+
+With no FILE, or when FILE is -, read standard input.
+
+  -a, --algo NAME   sha224, sha256 (default), sha384, sha512, sha512-224 or sha512-256
+  -b, --binary      read in binary mode, marked with '*' before the file name
+  -c, --check       read checksums from the FILEs and check them
+  -j, --jobs N      hash N files at a time, still printing them in order
+      --mmap        memory map the files instead of reading them, for large files
+  -t, --text        read in text mode (default)
+      --tree DIR    print one digest for DIR instead of hashing FILEs, can be repeated
+      --bench       measure the throughput of every algorithm
+      --help        display this help and exit
+
+When checking, GNU and BSD (tagged) lines are accepted. Without --algo, the algorithm
+is taken from the tag or the length of the hash.
+
+      --ignore-missing  don't fail or report status for missing files
+      --quiet           don't print OK for each successfully verified file
+      --status          don't output anything, the exit status shows success
+      --strict          exit non-zero for improperly formatted checksum lines
+
+The tree digest of a DIR is over the sorted paths, permissions and hashes of the
+regular files under it.
+
+  -L, --follow-symlinks     hash the targets of symbolic links instead of skipping them,
+                            except for broken links
+      --include GLOB        only hash the files matching one of the GLOBs
+      --exclude GLOB        skip the files and directories matching GLOB
+
+GLOBs without a '/' match file names, the others match paths relative to DIR.
+";
+
+/// What the command line asks for
+enum Command {
+    Hash(Options),
+    Tree(TreeArgs),
+    Bench,
+    Help,
 }
 
-/// Array of round constants. They are the first 32 bits of the cube roots of the first 64 primes in hex form
-const K: [u32; 64] = [
-    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
-    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
-    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
-    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
-    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
-    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
-    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
-    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
-];
-
-impl Sha256 {
-    /// Update state (self.h) using self.buffer.
-    ///
-    /// Assumes self.buffer is full
-    fn run_round(&mut self) {
-        // Create a 64-entry message schedule array w[0..63] of 32-bit words
-        let mut w = [0u32; 64];
-        // The initial values in w[0..63] don't matter, so many implementations zero them here
-        // Copy chunk into first 16 words w[0..15] of the message schedule array
-        self.buffer
-            .chunks(4)
-            .zip(w.iter_mut())
-            .for_each(|(buf, w)| *w = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]));
-
-        // Extend the first 16 words into the remaining 48 words w[16..63] of the message schedule array:
-        for i in 16..64 {
-            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
-            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
-
-            w[i] = w[i - 16]
-                .wrapping_add(s0)
-                .wrapping_add(w[i - 7])
-                .wrapping_add(s1);
-        }
-
-        // Initialize working variables to current hash value:
-        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.h.clone();
-
-        // Compression function main loop:
-        for i in 0..64 {
-            // Perform linear manipulation to shuffle the data further
-            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
-            let ch = (e & f) ^ ((!e) & g);
-            let temp1 = h
-                .wrapping_add(s1)
-                .wrapping_add(ch)
-                .wrapping_add(K[i])
-                .wrapping_add(w[i]);
-            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
-            let maj = (a & b) ^ (a & c) ^ (b & c);
-            let temp2 = s0.wrapping_add(maj);
-
-            // Assign results to temp variables
-            h = g;
-            g = f;
-            f = e;
-            e = d.wrapping_add(temp1);
-            d = c;
-            c = b;
-            b = a;
-            a = temp1.wrapping_add(temp2);
-        }
+/// Options for hashing or checking files, or hashing trees with `--tree`
+#[derive(Default)]
+struct Options {
+    algorithm: Option<Algorithm>,
+    binary: bool,
+    check: bool,
+    ignore_missing: bool,
+    quiet: bool,
+    status: bool,
+    strict: bool,
+    jobs: usize,
+    mmap: bool,
+    files: Vec<OsString>,
+    trees: Vec<OsString>,
+    tree_options: TreeOptions,
+}
 
-        // Add the compressed chunk to the current hash value:
-        self.h[0] = self.h[0].wrapping_add(a);
-        self.h[1] = self.h[1].wrapping_add(b);
-        self.h[2] = self.h[2].wrapping_add(c);
-        self.h[3] = self.h[3].wrapping_add(d);
-        self.h[4] = self.h[4].wrapping_add(e);
-        self.h[5] = self.h[5].wrapping_add(f);
-        self.h[6] = self.h[6].wrapping_add(g);
-        self.h[7] = self.h[7].wrapping_add(h);
-    }
+/// Options for hashing trees
+struct TreeArgs {
+    algorithm: Algorithm,
+    options: TreeOptions,
+    dirs: Vec<OsString>,
 }
 
-/// Updates the struct variables for the SHA256 hash
-/// Specifically, it calculates the length, buffer and filled variables
-impl Update for Sha256 {
-    fn update(&mut self, mut data: &[u8]) {
-        // Define variables
-        self.length += data.len() as u64;
-        let empty = &mut self.buffer[self.filled as usize..];
-        let len = data.len().min(empty.len());
-        empty[..len].copy_from_slice(&data[..len]);
-
-        // Make sure we have enough bits
-        if self.filled + len as u8 == 64 {
-            self.run_round();
-            data = &data[len..];
-
-            // Break up the data into 64 bit chunks if needed
-            while data.len() >= 64 {
-                self.buffer.copy_from_slice(&data[..64]);
-                self.run_round();
-                data = &data[64..];
-            }
-            self.buffer[..data.len()].copy_from_slice(data);
-            self.filled = data.len() as u8;
-        } else {
-            self.filled += data.len() as u8;
+/// Main function, hashes or checks the files given on the command line
+fn main() -> ExitCode {
+    let command = match parse_args(std::env::args_os().skip(1)) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}: {}", NAME, message);
+            eprintln!("Try '{} --help' for more information.", NAME);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match command {
+        Command::Hash(options) if options.check => check_manifests(&options),
+        Command::Hash(options) => hash_files(&options),
+        Command::Tree(tree) => hash_trees(&tree),
+        Command::Bench => {
+            bench::<Sha224>("SHA224");
+            bench::<Sha256>("SHA256");
+            bench::<Sha384>("SHA384");
+            bench::<Sha512>("SHA512");
+            bench::<Sha512_224>("SHA512/224");
+            bench::<Sha512_256>("SHA512/256");
+            ExitCode::SUCCESS
+        }
+        Command::Help => {
+            print!("{}", USAGE);
+            ExitCode::SUCCESS
         }
     }
 }
 
-// A list of zero bytes
-const ZERO_BYTES: [u8; 64] = [0u8; 64];
-
-/// Split up and pad the original message ahead of time for processing
-impl FixedOutput for Sha256 {
-    fn finalize_into(mut self, out: &mut digest::Output<Self>) {
-        // Calculate padding
-        let mut filled = self.filled as usize;
-        let length = self.length * 8;
-        Update::update(&mut self, &[0b10000000]);
-
-        // Update the struct
-        if filled > 64 - 8 {
-            Update::update(&mut self, &ZERO_BYTES[filled..]);
-            filled = 0;
-        }
+/// A command line argument
+enum Arg {
+    /// An option, with the value given inline as `--option=value`
+    Flag(String, Option<String>),
+    /// A file or directory
+    Operand(OsString),
+}
 
-        Update::update(&mut self, &ZERO_BYTES[filled..(64 - 8 - 1)]);
-        Update::update(&mut self, &length.to_be_bytes());
+/// Sorts the arguments into options and operands
+///
+/// "-" is an operand, and so is everything after "--".
+struct Args<I> {
+    args: I,
+    operands_only: bool,
+}
 
-        out.iter_mut()
-            .zip(self.h.iter().flat_map(|&h| h.to_be_bytes()))
-            .for_each(|(out, s)| *out = s);
+impl<I: Iterator<Item = OsString>> Args<I> {
+    /// The value of an option, inline or in the next argument
+    fn value(&mut self, flag: &str, inline: Option<String>) -> Result<String, String> {
+        self.path(flag, inline)
+            .map(|value| value.to_string_lossy().into_owned())
     }
-}
 
-/// Defines useful functions for our SHA256 struct
-impl Digest for Sha256 {
-    // Instantiate struct with the first 32 bits of the fractional parts of the square roots of the first 8 primes
-    fn new() -> Self {
-        Self {
-            h: [
-                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
-                0x5be0cd19,
-            ],
-            buffer: [0; 64],
-            filled: 0,
-            length: 0,
+    /// The value of an option naming a file, kept as it is when given in the next argument
+    fn path(&mut self, flag: &str, inline: Option<String>) -> Result<OsString, String> {
+        match inline {
+            Some(value) => Ok(value.into()),
+            None => self
+                .args
+                .next()
+                .ok_or(format!("option '{}' requires an argument", flag)),
         }
     }
 
-    // Helper methods to make our hash function simpler and more compact
-    fn update(&mut self, data: impl AsRef<[u8]>) {
-        <Self as Update>::update(self, data.as_ref())
+    /// The algorithm named by the value of `--algo`
+    fn algorithm(&mut self, flag: &str, inline: Option<String>) -> Result<Algorithm, String> {
+        let name = self.value(flag, inline)?;
+        Algorithm::from_name(&name).ok_or(format!("unknown algorithm '{}'", name))
     }
+}
 
-    fn finalize_into(self, out: &mut digest::Output<Self>) {
-        FixedOutput::finalize_into(self, out)
-    }
+impl<I: Iterator<Item = OsString>> Iterator for Args<I> {
+    type Item = Arg;
 
-    fn new_with_prefix(data: impl AsRef<[u8]>) -> Self {
-        Self::new().chain_update(data)
+    fn next(&mut self) -> Option<Arg> {
+        let arg = self.args.next()?;
+        if self.operands_only {
+            return Some(Arg::Operand(arg));
+        }
+        let Some(flag) = arg.to_str().filter(|a| a.starts_with('-') && *a != "-") else {
+            return Some(Arg::Operand(arg));
+        };
+        if flag == "--" {
+            self.operands_only = true;
+            return self.next();
+        }
+        Some(match flag.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                Arg::Flag(flag.to_string(), Some(value.to_string()))
+            }
+            _ => Arg::Flag(flag.to_string(), None),
+        })
     }
+}
 
-    fn chain_update(mut self, data: impl AsRef<[u8]>) -> Self {
-        Update::update(&mut self, data.as_ref());
-        self
+/// Parse the arguments, without the program name
+fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut mode_given = false;
+    let mut tree_flag = None;
+    let mut args = Args {
+        args: args.into_iter(),
+        operands_only: false,
+    };
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg {
+            Arg::Flag(flag, value) => (flag, value),
+            Arg::Operand(file) => {
+                options.files.push(file);
+                continue;
+            }
+        };
+        match flag.as_str() {
+            "-a" | "--algo" => options.algorithm = Some(args.algorithm(&flag, value)?),
+            "-b" | "--binary" => (options.binary, mode_given) = (true, true),
+            "-t" | "--text" => (options.binary, mode_given) = (false, true),
+            "-c" | "--check" => options.check = true,
+            "-j" | "--jobs" => options.jobs = parse_jobs(&args.value(&flag, value)?)?,
+            jobs if jobs.starts_with("-j") && !jobs.starts_with("--") => {
+                options.jobs = parse_jobs(&jobs[2..])?
+            }
+            "--mmap" if cfg!(feature = "mmap") => options.mmap = true,
+            "--mmap" => return Err("the --mmap option requires the mmap feature".into()),
+            "--ignore-missing" => options.ignore_missing = true,
+            "--quiet" => options.quiet = true,
+            "--status" => options.status = true,
+            "--strict" => options.strict = true,
+            "--tree" => options.trees.push(args.path(&flag, value)?),
+            "-L" | "--follow-symlinks" => {
+                options.tree_options.follow_symlinks = true;
+                tree_flag = Some(flag);
+            }
+            "--include" | "--exclude" => {
+                let pattern = args.value(&flag, value)?;
+                let result = if flag == "--include" {
+                    options.tree_options.include(&pattern)
+                } else {
+                    options.tree_options.exclude(&pattern)
+                };
+                result.map_err(|err| format!("invalid pattern '{}': {}", pattern, err))?;
+                tree_flag = Some(flag);
+            }
+            "--bench" => return Ok(Command::Bench),
+            "--help" => return Ok(Command::Help),
+            _ => return Err(format!("unrecognized option '{}'", flag)),
+        }
     }
 
-    fn finalize(self) -> digest::Output<Self> {
-        let mut tmp = digest::Output::<Self>::default();
-        <Self as FixedOutput>::finalize_into(self, &mut tmp);
-        tmp
+    if options.check && mode_given {
+        return Err(
+            "the --binary and --text options are meaningless when verifying checksums".into(),
+        );
     }
-
-    fn output_size() -> usize {
-        <Self as OutputSizeUser>::output_size()
+    if !options.check {
+        for (given, flag) in [
+            (options.ignore_missing, "--ignore-missing"),
+            (options.quiet, "--quiet"),
+            (options.status, "--status"),
+            (options.strict, "--strict"),
+        ] {
+            if given {
+                return Err(format!(
+                    "the {} option is meaningful only when verifying checksums",
+                    flag
+                ));
+            }
+        }
     }
 
-    fn digest(data: impl AsRef<[u8]>) -> digest::Output<Self> {
-        let mut s = Self::new();
-        <Self as Update>::update(&mut s, data.as_ref());
-        s.finalize()
+    if !options.trees.is_empty() {
+        if options.check || !options.files.is_empty() {
+            return Err("the --tree option can't be combined with --check or FILEs".into());
+        }
+        return Ok(Command::Tree(TreeArgs {
+            algorithm: options.algorithm.unwrap_or(Algorithm::Sha256),
+            options: options.tree_options,
+            dirs: options.trees,
+        }));
     }
-
-    fn finalize_reset(&mut self) -> digest::Output<Self>
-    where
-        Self: digest::FixedOutputReset,
-    {
-        std::mem::replace(self, Self::new()).finalize()
+    if let Some(flag) = tree_flag {
+        return Err(format!(
+            "the {} option is meaningful only with --tree",
+            flag
+        ));
     }
 
-    fn finalize_into_reset(&mut self, out: &mut digest::Output<Self>)
-    where
-        Self: digest::FixedOutputReset,
-    {
-        <Self as FixedOutputReset>::finalize_into_reset(self, out);
+    if options.files.is_empty() {
+        options.files.push("-".into());
     }
+    Ok(Command::Hash(options))
+}
 
-    fn reset(&mut self)
-    where
-        Self: digest::Reset,
-    {
-        *self = Self::new();
+/// Parse the number of jobs given to `-j`
+fn parse_jobs(jobs: &str) -> Result<usize, String> {
+    match jobs.parse() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err(format!("invalid number of jobs '{}'", jobs)),
     }
 }
 
-/// Defines the output size
-impl OutputSizeUser for Sha256 {
-    type OutputSize = digest::consts::U32;
+/// Print the tree digest of each directory
+fn hash_trees(tree: &TreeArgs) -> ExitCode {
+    let mut status = ExitCode::SUCCESS;
+    let mut stdout = io::stdout().lock();
+    for dir in &tree.dirs {
+        match tree.algorithm.hash_tree(dir.as_ref(), &tree.options) {
+            Ok(hash) => {
+                let line = manifest::format_line(&hash, dir.as_encoded_bytes(), false);
+                if stdout.write_all(&line).is_err() {
+                    return ExitCode::FAILURE;
+                }
+            }
+            Err(err) => {
+                report_error(dir, &err);
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
 }
 
-/// Reset function for our hash algorithm
-impl Reset for Sha256 {
-    fn reset(&mut self) {
-        *self = Self::new();
+/// Print a checksum line for each file, reporting the files that can't be read
+///
+/// With more than one job, the files are hashed on worker threads. Standard input is still read
+/// on this thread, in its place between the files.
+fn hash_files(options: &Options) -> ExitCode {
+    let algorithm = options.algorithm.unwrap_or(Algorithm::Sha256);
+    let mut failed = false;
+    let mut stdout = io::stdout().lock();
+    let mut print = |name: &OsStr, hash: io::Result<Vec<u8>>| match hash {
+        Ok(hash) => {
+            let line = manifest::format_line(&hash, name.as_encoded_bytes(), options.binary);
+            failed |= stdout.write_all(&line).is_err();
+        }
+        Err(err) => {
+            report_error(name, &err);
+            failed = true;
+        }
+    };
+
+    for (i, files) in options.files.split(|name| name == "-").enumerate() {
+        if i > 0 {
+            print("-".as_ref(), algorithm.hash_reader(io::stdin().lock()));
+        }
+        let hash = |path: &Path| hash_file(algorithm, path.as_os_str(), options.mmap);
+        if options.jobs > 1 {
+            sha_crypto::io::parallel_ordered(files, options.jobs, hash, |i, hash| {
+                print(&files[i], hash)
+            });
+        } else {
+            for name in files {
+                print(name, hash(name.as_ref()));
+            }
+        }
     }
-}
 
-/// Reset our algorithm's output
-impl FixedOutputReset for Sha256 {
-    fn finalize_into_reset(&mut self, out: &mut digest::Output<Self>) {
-        FixedOutput::finalize_into(std::mem::replace(self, Self::new()), out)
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
     }
 }
 
-/// Main function, not used
-fn main() {
-    println!("Welcome to our SHA2 family implementation!");
+/// Check every manifest, failing if any of them fails
+fn check_manifests(options: &Options) -> ExitCode {
+    let mut success = true;
+    for manifest in &options.files {
+        success &= check_manifest(manifest, options);
+    }
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
 }
 
-/// Tests for our SHA256 implementation
-#[cfg(test)]
-mod tests {
-    use digest::*;
-    use rand::Rng;
-
-    // Hash the given string with our algorithm and Rust's SHA256 algorithm
-    // If they are equal then we successfully hashed the string
-    macro_rules! sha_test {
-        ($i:literal) => {
-            let s = $i;
-            let my_res = crate::Sha256::new().chain_update(s.as_bytes()).finalize();
-            let ex_res = sha2::Sha256::new().chain_update(s.as_bytes()).finalize();
-            assert_eq!(
-                ex_res, my_res,
-                concat!("Failed to hash `", $i, "` correctly")
-            );
+/// Check the files listed in a manifest, printing their status and a summary of the problems
+///
+/// Returns false when coreutils would exit with a failure: a file doesn't match or can't be
+/// read, the manifest has no checksum lines, or with the stricter options.
+fn check_manifest(manifest: &OsStr, options: &Options) -> bool {
+    let display = if manifest == "-" {
+        "standard input".into()
+    } else {
+        manifest.to_string_lossy()
+    };
+    let contents = if manifest == "-" {
+        let mut contents = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut contents)
+            .map(|_| contents)
+    } else {
+        std::fs::read(manifest)
+    };
+    let contents = match contents {
+        Ok(contents) => contents,
+        Err(err) => {
+            report_error(manifest, &err);
+            return false;
+        }
+    };
+
+    let mut stdout = io::stdout().lock();
+    let (mut entries, mut invalid, mut unreadable, mut mismatched, mut matched) = (0, 0, 0, 0, 0);
+    for line in contents.split(|&b| b == b'\n') {
+        let entry = match manifest::parse_line(line, options.algorithm) {
+            Ok(Some(entry)) => entry,
+            Ok(None) => continue,
+            Err(_) => {
+                invalid += 1;
+                continue;
+            }
         };
-    }
+        entries += 1;
 
-    // Simple string tests
-    #[test]
-    fn simple() {
-        sha_test!("hello world");
-        sha_test!("Hello World!");
-        sha_test!("");
-        sha_test!("helloworld");
-        sha_test!("Some nice string");
-        sha_test!("Trying some numbers: 1234");
-        sha_test!("5678");
+        let path = entry_path(&entry);
+        let result = match hash_file(entry.algorithm, path.as_os_str(), options.mmap) {
+            Err(err) if options.ignore_missing && err.kind() == io::ErrorKind::NotFound => {
+                continue;
+            }
+            Err(err) => {
+                report_error(path.as_os_str(), &err);
+                unreadable += 1;
+                "FAILED open or read"
+            }
+            Ok(hash) if hash == entry.hash => {
+                matched += 1;
+                "OK"
+            }
+            Ok(_) => {
+                mismatched += 1;
+                "FAILED"
+            }
+        };
+        if options.status || (options.quiet && result == "OK") {
+            continue;
+        }
+
+        // Names are escaped like when they are hashed, and the line then starts with a backslash
+        let mut line = Vec::new();
+        match manifest::escape_name(&entry.name) {
+            Some(escaped) => {
+                line.push(b'\\');
+                line.extend(escaped);
+            }
+            None => line.extend_from_slice(&entry.name),
+        }
+        line.extend_from_slice(format!(": {}\n", result).as_bytes());
+        if stdout.write_all(&line).is_err() {
+            return false;
+        }
     }
 
-    // Randomly generate a list of numbers, hash them and check for equality
-    #[test]
-    fn rand() {
-        let mut rng = rand::thread_rng();
-        for _ in 0..10 {
-            let buffer: Vec<_> = (0..10).map(|_| rng.gen::<u8>()).collect();
-            let my_res = crate::Sha256::new().chain_update(&buffer).finalize();
-            let ex_res = sha2::Sha256::new().chain_update(&buffer).finalize();
-            assert_eq!(ex_res, my_res, "Failed on random test");
+    if entries == 0 {
+        eprintln!(
+            "{}: {}: no properly formatted checksum lines found",
+            NAME, display
+        );
+        return false;
+    }
+    if !options.status {
+        if invalid > 0 {
+            let lines = plural(invalid, "line is", "lines are");
+            eprintln!("{}: WARNING: {} improperly formatted", NAME, lines);
+        }
+        if unreadable > 0 {
+            let files = plural(unreadable, "listed file", "listed files");
+            eprintln!("{}: WARNING: {} could not be read", NAME, files);
+        }
+        if mismatched > 0 {
+            let checksums = plural(mismatched, "computed checksum", "computed checksums");
+            eprintln!("{}: WARNING: {} did NOT match", NAME, checksums);
         }
     }
+    if options.ignore_missing && matched == 0 && mismatched == 0 && unreadable == 0 {
+        eprintln!("{}: {}: no file was verified", NAME, display);
+        return false;
+    }
+    mismatched == 0 && unreadable == 0 && !(options.strict && invalid > 0)
+}
+
+/// Hash a file, or standard input for "-", memory mapping it with `mmap`
+fn hash_file(algorithm: Algorithm, name: &OsStr, mmap: bool) -> io::Result<Vec<u8>> {
+    if name == "-" {
+        return algorithm.hash_reader(io::stdin().lock());
+    }
+    #[cfg(feature = "mmap")]
+    if mmap {
+        return algorithm.hash_file_mmap(name.as_ref());
+    }
+    #[cfg(not(feature = "mmap"))]
+    debug_assert!(!mmap, "--mmap is rejected without the mmap feature");
+    algorithm.hash_file(name.as_ref())
+}
+
+/// The path of a file listed in a manifest
+fn entry_path(entry: &Entry) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        OsStr::from_bytes(&entry.name).into()
+    }
+    #[cfg(not(unix))]
+    {
+        String::from_utf8_lossy(&entry.name).into_owned().into()
+    }
+}
+
+/// Print the error for a file, like coreutils: without the "(os error N)" suffix
+fn report_error(name: &OsStr, err: &io::Error) {
+    let message = err.to_string();
+    let message = match message.find(" (os error") {
+        Some(end) => &message[..end],
+        None => &message,
+    };
+    eprintln!("{}: {}: {}", NAME, name.to_string_lossy(), message);
+}
+
+/// Pick the singular or plural form for a count, and put the count in front
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("{} {}", count, if count == 1 { one } else { many })
+}
+
+/// Print the throughput of an algorithm, hashing a large message in one update and block by block
+///
+/// The block by block updates start one byte off the block boundary, so every block goes through
+/// the internal buffer like it did before full blocks were compressed in place.
+fn bench<D: Digest + BlockSizeUser>(name: &str) {
+    let data = vec![0x5a; BENCH_SIZE];
+
+    let start = Instant::now();
+    let in_place = D::digest(&data);
+    let in_place_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut hasher = D::new();
+    hasher.update(&data[..1]);
+    data[1..]
+        .chunks(D::block_size())
+        .for_each(|block| hasher.update(block));
+    let buffered = hasher.finalize();
+    let buffered_time = start.elapsed();
+    assert_eq!(in_place, buffered);
+
+    let mib = (BENCH_SIZE >> 20) as f64;
+    println!(
+        "{}: {:.0} MiB/s in place, {:.0} MiB/s buffered",
+        name,
+        mib / in_place_time.as_secs_f64(),
+        mib / buffered_time.as_secs_f64()
+    );
 }
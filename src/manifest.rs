@@ -0,0 +1,271 @@
+use crate::Algorithm;
+use std::fmt;
+
+/// One checksum line of a manifest, in the format of GNU coreutils or BSD
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+    /// The algorithm given by the tag, or guessed from the length of the hash
+    pub algorithm: Algorithm,
+    /// The expected hash of the file
+    pub hash: Vec<u8>,
+    /// The name of the file, with any escapes decoded
+    pub name: Vec<u8>,
+    /// If the file was hashed in binary mode (marked with `*`)
+    pub binary: bool,
+}
+
+/// Error for a line that isn't a checksum line in any of the supported formats
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InvalidLine;
+
+impl fmt::Display for InvalidLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("improperly formatted checksum line")
+    }
+}
+
+impl std::error::Error for InvalidLine {}
+
+/// Parse one line of a manifest, with or without its line break
+///
+/// Both GNU lines (`<hex>  <name>`, or `<hex> *<name>` for binary mode) and BSD tagged lines
+/// (`SHA256 (<name>) = <hex>`) are accepted. A GNU line starting with a backslash has an escaped
+/// name, like [`format_line`] writes. When `algorithm` is given, lines for any other algorithm
+/// are rejected, otherwise the algorithm comes from the tag or the length of the hash.
+///
+/// Blank lines and comments (starting with `#`) give `Ok(None)`.
+pub fn parse_line(
+    mut line: &[u8],
+    algorithm: Option<Algorithm>,
+) -> Result<Option<Entry>, InvalidLine> {
+    line = line.strip_suffix(b"\n").unwrap_or(line);
+    line = line.strip_suffix(b"\r").unwrap_or(line);
+    while let [b' ' | b'\t', rest @ ..] = line {
+        line = rest;
+    }
+    if line.is_empty() || line[0] == b'#' {
+        return Ok(None);
+    }
+
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let mut entry = parse_tagged(line, algorithm)
+        .or_else(|| parse_untagged(line, algorithm))
+        .ok_or(InvalidLine)?;
+    if escaped {
+        entry.name = unescape(&entry.name).ok_or(InvalidLine)?;
+    }
+    Ok(Some(entry))
+}
+
+/// Parse a BSD tagged line, `<tag> (<name>) = <hex>`
+fn parse_tagged(line: &[u8], algorithm: Option<Algorithm>) -> Option<Entry> {
+    let tag_end = line.windows(2).position(|w| w == b" (")?;
+    let tag = std::str::from_utf8(&line[..tag_end]).ok()?;
+    let tagged = Algorithm::from_name(tag)?;
+    if algorithm.is_some_and(|algorithm| algorithm != tagged) {
+        return None;
+    }
+
+    // The name may contain ") = " itself, so the hash starts after the last one
+    let rest = &line[tag_end + 2..];
+    let name_end = rest.windows(4).rposition(|w| w == b") = ")?;
+    let hash = decode_hex(&rest[name_end + 4..])?;
+    if hash.len() != tagged.output_size() {
+        return None;
+    }
+    Some(Entry {
+        algorithm: tagged,
+        hash,
+        name: rest[..name_end].to_vec(),
+        binary: false,
+    })
+}
+
+/// Parse a GNU line, `<hex>  <name>` or `<hex> *<name>`
+fn parse_untagged(line: &[u8], algorithm: Option<Algorithm>) -> Option<Entry> {
+    let hex_end = line.iter().position(|b| !b.is_ascii_hexdigit())?;
+    let hash = decode_hex(&line[..hex_end])?;
+    let algorithm = match algorithm {
+        Some(algorithm) if algorithm.output_size() == hash.len() => algorithm,
+        Some(_) => return None,
+        None => Algorithm::from_output_size(hash.len())?,
+    };
+
+    let (binary, name) = match &line[hex_end..] {
+        [b' ', b' ', name @ ..] => (false, name),
+        [b' ', b'*', name @ ..] => (true, name),
+        _ => return None,
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some(Entry {
+        algorithm,
+        hash,
+        name: name.to_vec(),
+        binary,
+    })
+}
+
+/// Format a checksum line like GNU coreutils: `<hex> <mode><name>`, with a line break
+///
+/// The mode is `*` for binary mode and a space for text mode. Names containing a backslash or a
+/// line break are escaped with [`escape_name`], and the line then starts with a backslash.
+pub fn format_line(hash: &[u8], name: &[u8], binary: bool) -> Vec<u8> {
+    let escaped = escape_name(name);
+    let name = escaped.as_deref().unwrap_or(name);
+
+    let mut line = Vec::with_capacity(hash.len() * 2 + name.len() + 4);
+    if escaped.is_some() {
+        line.push(b'\\');
+    }
+    for byte in hash {
+        line.extend_from_slice(format!("{:02x}", byte).as_bytes());
+    }
+    line.push(b' ');
+    line.push(if binary { b'*' } else { b' ' });
+    line.extend_from_slice(name);
+    line.push(b'\n');
+    line
+}
+
+/// Escape the backslashes and line breaks of a file name, if it has any
+///
+/// Returns `None` when the name can be written as it is.
+pub fn escape_name(name: &[u8]) -> Option<Vec<u8>> {
+    if !name.iter().any(|b| matches!(b, b'\\' | b'\n' | b'\r')) {
+        return None;
+    }
+    let mut escaped = Vec::with_capacity(name.len() + 2);
+    for &byte in name {
+        match byte {
+            b'\\' => escaped.extend_from_slice(b"\\\\"),
+            b'\n' => escaped.extend_from_slice(b"\\n"),
+            b'\r' => escaped.extend_from_slice(b"\\r"),
+            _ => escaped.push(byte),
+        }
+    }
+    Some(escaped)
+}
+
+/// Decode a name escaped by [`escape_name`], failing on unknown escapes
+fn unescape(name: &[u8]) -> Option<Vec<u8>> {
+    let mut unescaped = Vec::with_capacity(name.len());
+    let mut bytes = name.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'\\' {
+            unescaped.push(byte);
+            continue;
+        }
+        unescaped.push(match bytes.next()? {
+            b'\\' => b'\\',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            _ => return None,
+        });
+    }
+    Some(unescaped)
+}
+
+/// Decode a hash written in hex, in either case
+fn decode_hex(hex: &[u8]) -> Option<Vec<u8>> {
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Tests for reading and writing manifests
+#[cfg(test)]
+mod tests {
+    use super::{format_line, parse_line, Entry, InvalidLine};
+    use crate::test_util::hex;
+    use crate::Algorithm;
+
+    const HASH_256: &str = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+    const HASH_224: &str = "abd37534c7d9a2efb9465de931cd7055ffdb8879563ae98078d6d6d5";
+
+    fn entry(algorithm: Algorithm, hash: &str, name: &[u8], binary: bool) -> Option<Entry> {
+        Some(Entry {
+            algorithm,
+            hash: hex(hash),
+            name: name.to_vec(),
+            binary,
+        })
+    }
+
+    // GNU and BSD lines, with the algorithm taken from the tag or the length of the hash
+    #[test]
+    fn formats() {
+        let line = format!("{}  a file\n", HASH_256);
+        let expected = entry(Algorithm::Sha256, HASH_256, b"a file", false);
+        assert_eq!(parse_line(line.as_bytes(), None), Ok(expected.clone()));
+        assert_eq!(
+            parse_line(line.as_bytes(), Some(Algorithm::Sha256)),
+            Ok(expected)
+        );
+
+        let line = format!("{} *a\r\n", HASH_224.to_uppercase());
+        let expected = entry(Algorithm::Sha224, HASH_224, b"a", true);
+        assert_eq!(parse_line(line.as_bytes(), None), Ok(expected));
+        let expected = entry(Algorithm::Sha512_224, HASH_224, b"a", true);
+        let algorithm = Some(Algorithm::Sha512_224);
+        assert_eq!(parse_line(line.as_bytes(), algorithm), Ok(expected));
+
+        let line = format!("SHA512t224 (a (b) = c) = {}", HASH_224);
+        let expected = entry(Algorithm::Sha512_224, HASH_224, b"a (b) = c", false);
+        assert_eq!(parse_line(line.as_bytes(), None), Ok(expected));
+    }
+
+    // Escaped names are decoded, and format_line escapes them back
+    #[test]
+    fn escapes() {
+        let name = b"back\\slash\nnew line";
+        let line = format_line(&hex(HASH_256), name, false);
+        let expected = format!("\\{}  back\\\\slash\\nnew line\n", HASH_256);
+        assert_eq!(line, expected.as_bytes());
+        let expected = entry(Algorithm::Sha256, HASH_256, name, false);
+        assert_eq!(parse_line(&line, None), Ok(expected));
+
+        let line = format!("\\{}  bad\\escape", HASH_256);
+        assert_eq!(parse_line(line.as_bytes(), None), Err(InvalidLine));
+    }
+
+    // Blank lines and comments are skipped, anything else that doesn't parse is rejected
+    #[test]
+    fn invalid() {
+        for line in ["", "\n", "  \r\n", "# a comment"] {
+            assert_eq!(parse_line(line.as_bytes(), None), Ok(None), "{:?}", line);
+        }
+        for line in [
+            "garbage".to_string(),
+            format!("{} a", HASH_256),
+            format!("{}  ", HASH_256),
+            format!("{}  a", &HASH_256[1..]),
+            format!("{}0  a", HASH_256),
+            format!("SHA384 (a) = {}", HASH_256),
+            format!("MD5 (a) = {}", HASH_256),
+            format!("SHA256 (a) = {}", &HASH_256[2..]),
+        ] {
+            assert_eq!(
+                parse_line(line.as_bytes(), None),
+                Err(InvalidLine),
+                "{}",
+                line
+            );
+        }
+
+        // A line for another algorithm than the one expected
+        let line = format!("SHA256 (a) = {}", HASH_256);
+        let algorithm = Some(Algorithm::Sha512_256);
+        assert_eq!(parse_line(line.as_bytes(), algorithm), Err(InvalidLine));
+        let line = format!("{}  a", HASH_256);
+        let algorithm = Some(Algorithm::Sha512);
+        assert_eq!(parse_line(line.as_bytes(), algorithm), Err(InvalidLine));
+    }
+}
@@ -0,0 +1,438 @@
+//! Merkle trees following RFC 6962, for append-only logs
+//!
+//! Leaves are hashed with a `0x00` prefix and inner nodes with a `0x01` prefix, so a leaf can't
+//! be passed off as a node. The log proves that an entry is in the tree with an inclusion proof,
+//! and that a newer tree still contains an older one with a consistency proof:
+//!
+//! ```
+//! use sha_crypto::merkle::{verify_consistency, verify_inclusion, MerkleTree};
+//! use sha_crypto::Sha256;
+//!
+//! let mut tree = MerkleTree::<Sha256>::new();
+//! for entry in ["a", "b", "c"] {
+//!     tree.append(entry.as_bytes());
+//! }
+//! let old_root = tree.root();
+//! let index = tree.append(b"d");
+//!
+//! let proof = tree.inclusion_proof(index, tree.len()).unwrap();
+//! assert!(verify_inclusion::<Sha256>(b"d", index, tree.len(), &proof, &tree.root()));
+//! let proof = tree.consistency_proof(3, tree.len()).unwrap();
+//! assert!(verify_consistency::<Sha256>(3, tree.len(), &old_root, &tree.root(), &proof));
+//! ```
+
+use alloc::vec::Vec;
+use digest::{Digest, Output};
+
+/// The hash of a leaf, `H(0x00 || data)`
+pub fn leaf_hash<D: Digest>(data: &[u8]) -> Output<D> {
+    D::new().chain_update([0]).chain_update(data).finalize()
+}
+
+/// The hash of an inner node, `H(0x01 || left || right)`
+pub fn node_hash<D: Digest>(left: &Output<D>, right: &Output<D>) -> Output<D> {
+    D::new()
+        .chain_update([1])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+}
+
+/// A Merkle tree that leaves are appended to
+///
+/// Every complete subtree is kept, so appending, the roots of the current and older sizes, and
+/// the proofs all take a logarithmic number of hashes.
+#[derive(Clone)]
+pub struct MerkleTree<D: Digest> {
+    /// `levels[k][i]` is the hash of the complete subtree of the leaves `i << k` to
+    /// `(i + 1) << k`, and `levels[0]` holds the leaf hashes
+    levels: Vec<Vec<Output<D>>>,
+}
+
+impl<D: Digest> Default for MerkleTree<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// An empty tree
+    pub fn new() -> Self {
+        Self { levels: Vec::new() }
+    }
+
+    /// The number of leaves
+    pub fn len(&self) -> usize {
+        self.levels.first().map_or(0, Vec::len)
+    }
+
+    /// If the tree has no leaves
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append a leaf for `data`, returning its index
+    pub fn append(&mut self, data: &[u8]) -> usize {
+        let index = self.len();
+        let mut hash = leaf_hash::<D>(data);
+        for level in 0.. {
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            let hashes = &mut self.levels[level];
+            hashes.push(hash);
+
+            // A new complete subtree on the level above, when this one has a pair
+            if hashes.len() % 2 == 1 {
+                break;
+            }
+            hash = node_hash::<D>(&hashes[hashes.len() - 2], &hashes[hashes.len() - 1]);
+        }
+        index
+    }
+
+    /// The root hash of the tree, the hash of an empty string for an empty tree
+    pub fn root(&self) -> Output<D> {
+        self.subtree(0, self.len())
+    }
+
+    /// The root hash the tree had when it had `size` leaves, if it had that many
+    pub fn root_at(&self, size: usize) -> Option<Output<D>> {
+        (size <= self.len()).then(|| self.subtree(0, size))
+    }
+
+    /// Proof that the leaf at `index` is in the tree of the first `size` leaves
+    ///
+    /// Returns `None` unless `index < size <= self.len()`.
+    pub fn inclusion_proof(&self, index: usize, size: usize) -> Option<Vec<Output<D>>> {
+        if index >= size || size > self.len() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        self.path(index, 0, size, &mut proof);
+        Some(proof)
+    }
+
+    /// Proof that the tree of the first `old_size` leaves is a prefix of the tree of the first
+    /// `new_size` leaves
+    ///
+    /// The proof is empty when either tree is empty or both have the same size. Returns `None`
+    /// unless `old_size <= new_size <= self.len()`.
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Option<Vec<Output<D>>> {
+        if old_size > new_size || new_size > self.len() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        if old_size > 0 {
+            self.subproof(old_size, 0, new_size, true, &mut proof);
+        }
+        Some(proof)
+    }
+
+    /// The hash of the leaves from `start` to `end`, `MTH(D[start:end])` in the RFC
+    ///
+    /// The ranges the RFC splits a tree into always start on a multiple of the largest power of
+    /// two they contain, so they are either complete subtrees or a complete subtree followed by
+    /// a smaller range.
+    fn subtree(&self, start: usize, end: usize) -> Output<D> {
+        let size = end - start;
+        if size == 0 {
+            return D::digest([]);
+        }
+        if size.is_power_of_two() {
+            let level = size.trailing_zeros() as usize;
+            return self.levels[level][start >> level].clone();
+        }
+        let split = start + split_point(size);
+        node_hash::<D>(&self.subtree(start, split), &self.subtree(split, end))
+    }
+
+    /// The inclusion proof of leaf `index` in the leaves `start` to `end`, `PATH` in the RFC
+    fn path(&self, index: usize, start: usize, end: usize, proof: &mut Vec<Output<D>>) {
+        if end - start <= 1 {
+            return;
+        }
+        let split = start + split_point(end - start);
+        if index < split {
+            self.path(index, start, split, proof);
+            proof.push(self.subtree(split, end));
+        } else {
+            self.path(index, split, end, proof);
+            proof.push(self.subtree(start, split));
+        }
+    }
+
+    /// The consistency proof of the first `old_size` leaves of the leaves `start` to `end`,
+    /// `SUBPROOF` in the RFC
+    ///
+    /// `complete` is set while the old tree's root is the root of a subtree the verifier already
+    /// has, so it can be left out of the proof.
+    fn subproof(
+        &self,
+        old_size: usize,
+        start: usize,
+        end: usize,
+        complete: bool,
+        proof: &mut Vec<Output<D>>,
+    ) {
+        if old_size == end - start {
+            if !complete {
+                proof.push(self.subtree(start, end));
+            }
+            return;
+        }
+        let split = split_point(end - start);
+        if old_size <= split {
+            self.subproof(old_size, start, start + split, complete, proof);
+            proof.push(self.subtree(start + split, end));
+        } else {
+            self.subproof(old_size - split, start + split, end, false, proof);
+            proof.push(self.subtree(start, start + split));
+        }
+    }
+}
+
+/// Check an inclusion proof for the leaf `data` at `index`, in a tree of `size` leaves with the
+/// given root
+pub fn verify_inclusion<D: Digest>(
+    data: &[u8],
+    index: usize,
+    size: usize,
+    proof: &[Output<D>],
+    root: &Output<D>,
+) -> bool {
+    if index >= size {
+        return false;
+    }
+
+    // Walk up from the leaf, with `last` the index of the last node on each level (RFC 9162)
+    let (mut index, mut last) = (index, size - 1);
+    let mut hash = leaf_hash::<D>(data);
+    for sibling in proof {
+        if last == 0 {
+            return false;
+        }
+        if index % 2 == 1 || index == last {
+            hash = node_hash::<D>(sibling, &hash);
+            // A node without a sibling on its right moves up unchanged
+            while index % 2 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            hash = node_hash::<D>(&hash, sibling);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+    last == 0 && hash == *root
+}
+
+/// Check a consistency proof between a tree of `old_size` leaves and a tree of `new_size` leaves
+pub fn verify_consistency<D: Digest>(
+    old_size: usize,
+    new_size: usize,
+    old_root: &Output<D>,
+    new_root: &Output<D>,
+    proof: &[Output<D>],
+) -> bool {
+    if old_size > new_size {
+        return false;
+    }
+    if old_size == new_size || old_size == 0 {
+        let old_matches = old_size > 0 || *old_root == D::digest([]);
+        return proof.is_empty() && old_matches && (old_size == 0 || old_root == new_root);
+    }
+
+    // When the old tree is a complete subtree, its root is the first node of the proof
+    let mut nodes = proof.iter();
+    let first = if old_size.is_power_of_two() {
+        old_root
+    } else {
+        match nodes.next() {
+            Some(first) => first,
+            None => return false,
+        }
+    };
+
+    // Rebuild both roots from the proof (RFC 9162), from the last node of the old tree
+    let (mut index, mut last) = (old_size - 1, new_size - 1);
+    while index % 2 == 1 {
+        index >>= 1;
+        last >>= 1;
+    }
+    let (mut old_hash, mut new_hash) = (first.clone(), first.clone());
+    for node in nodes {
+        if last == 0 {
+            return false;
+        }
+        if index % 2 == 1 || index == last {
+            old_hash = node_hash::<D>(node, &old_hash);
+            new_hash = node_hash::<D>(node, &new_hash);
+            while index % 2 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            new_hash = node_hash::<D>(&new_hash, node);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+    last == 0 && old_hash == *old_root && new_hash == *new_root
+}
+
+/// The size of the left subtree of a tree of `size > 1` leaves: the largest power of two below it
+fn split_point(size: usize) -> usize {
+    1 << (usize::BITS - 1 - (size - 1).leading_zeros())
+}
+
+/// Tests for the Merkle trees, with vectors computed from the definitions in RFC 6962
+#[cfg(test)]
+mod tests {
+    use super::{verify_consistency, verify_inclusion, MerkleTree};
+    use crate::test_util::hex;
+    use crate::Sha256;
+
+    /// The leaves of the test vectors of the certificate transparency implementations
+    const LEAVES: [&[u8]; 8] = [
+        b"",
+        b"\x00",
+        b"\x10",
+        b"\x20\x21",
+        b"\x30\x31",
+        b"\x40\x41\x42\x43",
+        b"\x50\x51\x52\x53\x54\x55\x56\x57",
+        b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+    ];
+
+    /// A tree of the test leaves
+    fn tree() -> MerkleTree<Sha256> {
+        let mut tree = MerkleTree::new();
+        for (i, leaf) in LEAVES.iter().enumerate() {
+            assert_eq!(tree.append(leaf), i);
+        }
+        tree
+    }
+
+    /// Decode the nodes of a proof
+    fn nodes(proof: &[&str]) -> Vec<Vec<u8>> {
+        proof.iter().map(|node| hex(node)).collect()
+    }
+
+    // The root after each append, and the roots of the older sizes
+    #[test]
+    fn roots() {
+        let roots = [
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+            "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+            "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+        ];
+        let mut tree = MerkleTree::<Sha256>::new();
+        assert!(tree.is_empty());
+        assert_eq!(tree.root()[..], hex(roots[0]));
+        for (leaf, root) in LEAVES.iter().zip(&roots[1..]) {
+            tree.append(leaf);
+            assert_eq!(tree.root()[..], hex(root));
+        }
+        for (size, root) in roots.iter().enumerate() {
+            assert_eq!(tree.root_at(size).unwrap()[..], hex(root));
+        }
+        assert_eq!(tree.root_at(9), None);
+    }
+
+    // Inclusion proofs match the vectors, verify for every leaf, and fail for anything else
+    #[test]
+    fn inclusion() {
+        let tree = tree();
+        let proof = tree.inclusion_proof(5, 8).unwrap();
+        let expected = nodes(&[
+            "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+            "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        ]);
+        assert_eq!(
+            proof.iter().map(|n| n.to_vec()).collect::<Vec<_>>(),
+            expected
+        );
+        let proof = tree.inclusion_proof(2, 3).unwrap();
+        let expected = nodes(&["fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125"]);
+        assert_eq!(
+            proof.iter().map(|n| n.to_vec()).collect::<Vec<_>>(),
+            expected
+        );
+
+        for size in 1..=8 {
+            let root = tree.root_at(size).unwrap();
+            let old_root = tree.root_at(size - 1).unwrap();
+            let verify = |leaf: &[u8], index, proof: &[_], root: &_| {
+                verify_inclusion::<Sha256>(leaf, index, size, proof, root)
+            };
+            for (index, leaf) in LEAVES[..size].iter().enumerate() {
+                let proof = tree.inclusion_proof(index, size).unwrap();
+                assert!(verify(leaf, index, &proof, &root));
+                assert!(!verify(b"x", index, &proof, &root));
+                assert!(!verify(leaf, index, &proof, &old_root));
+                if size > 1 {
+                    assert!(!verify(leaf, (index + 1) % size, &proof, &root));
+                    assert!(!verify(leaf, index, &proof[1..], &root));
+                }
+            }
+        }
+        assert_eq!(tree.inclusion_proof(3, 3), None);
+        assert_eq!(tree.inclusion_proof(0, 9), None);
+    }
+
+    // Consistency proofs match the vectors, verify for every pair of sizes, and fail otherwise
+    #[test]
+    fn consistency() {
+        let tree = tree();
+        let proof = tree.consistency_proof(6, 8).unwrap();
+        let expected = nodes(&[
+            "0ebc5d3437fbe2db158b9f126a1d118e308181031d0a949f8dededebc558ef6a",
+            "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        ]);
+        assert_eq!(
+            proof.iter().map(|n| n.to_vec()).collect::<Vec<_>>(),
+            expected
+        );
+        let proof = tree.consistency_proof(2, 5).unwrap();
+        let expected = nodes(&[
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+        ]);
+        assert_eq!(
+            proof.iter().map(|n| n.to_vec()).collect::<Vec<_>>(),
+            expected
+        );
+
+        for new_size in 0..=8 {
+            let new_root = tree.root_at(new_size).unwrap();
+            for old_size in 0..=new_size {
+                let old_root = tree.root_at(old_size).unwrap();
+                let proof = tree.consistency_proof(old_size, new_size).unwrap();
+                let verify = |old_size, old_root: &_, new_root: &_, proof: &[_]| {
+                    verify_consistency::<Sha256>(old_size, new_size, old_root, new_root, proof)
+                };
+                assert!(verify(old_size, &old_root, &new_root, &proof));
+                if old_size == 0 || old_size == new_size {
+                    assert!(proof.is_empty());
+                    continue;
+                }
+                assert!(!verify(old_size, &new_root, &new_root, &proof));
+                assert!(!verify(old_size, &old_root, &old_root, &proof));
+                assert!(!verify(old_size + 1, &old_root, &new_root, &proof));
+                assert!(!verify(old_size, &old_root, &new_root, &proof[1..]));
+            }
+        }
+        assert_eq!(tree.consistency_proof(3, 2), None);
+        assert_eq!(tree.consistency_proof(3, 9), None);
+    }
+}
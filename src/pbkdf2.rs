@@ -0,0 +1,117 @@
+use crate::hmac::Hmac;
+use digest::core_api::BlockSizeUser;
+use digest::{Digest, Mac, OutputSizeUser};
+
+/// PBKDF2 (RFC 8018) with HMAC over one of the SHA2 hashers, eg. `pbkdf2_hmac::<Sha256>`
+///
+/// Fills `out` with key material derived from the password and salt, using `rounds`
+/// iterations per block of output. Any output length can be requested.
+///
+/// The HMAC is keyed once per call, so every iteration only runs the compression function on the
+/// previous block instead of hashing the password again.
+///
+/// # Panics
+///
+/// Panics if `rounds` is 0
+pub fn pbkdf2_hmac<D: Digest + BlockSizeUser + Clone>(
+    password: &[u8],
+    salt: &[u8],
+    rounds: u32,
+    out: &mut [u8],
+) {
+    assert!(rounds > 0, "PBKDF2 needs at least one round");
+    let prf = Hmac::<D>::new_from_slice(password).unwrap();
+    let hash_len = <D as OutputSizeUser>::output_size();
+
+    for (i, chunk) in out.chunks_mut(hash_len).enumerate() {
+        // U_1 = PRF(password, salt | i), with i starting at 1
+        let mut mac = prf.clone();
+        mac.update(salt);
+        mac.update(&(i as u32 + 1).to_be_bytes());
+        let mut u = mac.finalize().into_bytes();
+        let mut block = u.clone();
+
+        // U_j = PRF(password, U_{j - 1}), and the block is the xor of every U_j
+        for _ in 1..rounds {
+            let mut mac = prf.clone();
+            mac.update(&u);
+            u = mac.finalize().into_bytes();
+            block.iter_mut().zip(u.iter()).for_each(|(b, u)| *b ^= u);
+        }
+
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+}
+
+/// Tests for our PBKDF2 implementation
+#[cfg(test)]
+mod tests {
+    use super::pbkdf2_hmac;
+    use crate::test_util::hex;
+    use crate::{Sha256, Sha512};
+
+    // Derive a key and compare it with the expected output
+    macro_rules! pbkdf2_test {
+        ($hash:ty, $password:literal, $salt:literal, $rounds:literal, $expected:literal) => {
+            let expected = hex($expected);
+            let mut out = vec![0u8; expected.len()];
+            pbkdf2_hmac::<$hash>($password, $salt, $rounds, &mut out);
+            assert_eq!(
+                expected, out,
+                concat!("Failed to derive a key from `", stringify!($password), "`")
+            );
+        };
+    }
+
+    // The PBKDF2-HMAC-SHA256 test vectors from RFC 7914
+    #[test]
+    fn rfc7914() {
+        pbkdf2_test!(
+            Sha256,
+            b"passwd",
+            b"salt",
+            1,
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+             49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"
+        );
+        pbkdf2_test!(
+            Sha256,
+            b"Password",
+            b"NaCl",
+            80000,
+            "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56\
+             a1d425a1225833549adb841b51c9b3176a272bdebba1d078478f62b397f33c8d"
+        );
+    }
+
+    // Output lengths that aren't a multiple of the hash length, checked against an independent
+    // implementation
+    #[test]
+    fn partial_blocks() {
+        pbkdf2_test!(
+            Sha256,
+            b"password",
+            b"salt",
+            4096,
+            "c5e478d59288c841aa530db6845c4c8d962893a0"
+        );
+        pbkdf2_test!(
+            Sha512,
+            b"password",
+            b"salt",
+            1,
+            "867f70cf1ade02cff3752599a3a53dc4af34c7a669815ae5d513554e1c8cf252\
+             c02d470a285a0501bad999bfe943c08f050235d7d68b1da55e63f73b60a57fce"
+        );
+        pbkdf2_test!(
+            Sha512,
+            b"passwordPASSWORDpassword",
+            b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+            4096,
+            "8c0511f4c6e597c6ac6315d8f0362e225f3c501495ba23b868c005174dc4ee71\
+             115b59f9e60cd9532fa33e0f75aefe30225c583a186cd82bd4daea9724a3d3b8\
+             04f75bdd41494fa324cab24bcc680fb3b96a30cf5d21fac3c2875913919f3399\
+             b1d9ce7e"
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use crate::engine256::Engine256;
+use digest::consts::{U32, U64};
+
+/// Struct for SHA256
+#[derive(Clone)]
+pub struct Sha256 {
+    engine: Engine256,
+}
+
+/// Initial hash values: the first 32 bits of the fractional parts of the square roots of the first 8 primes
+pub(crate) const IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+impl_sha!(Sha256, Engine256, IV, U32, U64);
+
+/// Tests for our SHA256 implementation
+#[cfg(test)]
+mod tests {
+    use digest::*;
+    use rand::Rng;
+
+    // Hash the given string with our algorithm and Rust's SHA256 algorithm
+    // If they are equal then we successfully hashed the string
+    macro_rules! sha_test {
+        ($i:literal) => {
+            let s = $i;
+            let my_res = super::Sha256::new().chain_update(s.as_bytes()).finalize();
+            let ex_res = sha2::Sha256::new().chain_update(s.as_bytes()).finalize();
+            assert_eq!(
+                ex_res, my_res,
+                concat!("Failed to hash `", $i, "` correctly")
+            );
+        };
+    }
+
+    // Simple string tests
+    #[test]
+    fn simple() {
+        sha_test!("hello world");
+        sha_test!("Hello World!");
+        sha_test!("");
+        sha_test!("helloworld");
+        sha_test!("Some nice string");
+        sha_test!("Trying some numbers: 1234");
+        sha_test!("5678");
+    }
+
+    // Hash every length around the padding boundaries of the first few blocks
+    #[test]
+    fn lengths() {
+        let buffer: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        for len in 0..buffer.len() {
+            let my_res = super::Sha256::new().chain_update(&buffer[..len]).finalize();
+            let ex_res = sha2::Sha256::new().chain_update(&buffer[..len]).finalize();
+            assert_eq!(ex_res, my_res, "Failed to hash {} bytes correctly", len);
+        }
+    }
+
+    // Randomly generate a list of numbers, hash them and check for equality
+    #[test]
+    fn rand() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let buffer: Vec<_> = (0..10).map(|_| rng.gen::<u8>()).collect();
+            let my_res = super::Sha256::new().chain_update(&buffer).finalize();
+            let ex_res = sha2::Sha256::new().chain_update(&buffer).finalize();
+            assert_eq!(ex_res, my_res, "Failed on random test");
+        }
+    }
+
+    // Messages that aren't a whole number of bytes, in the format of the bit-oriented NIST CAVP
+    // vectors (SHA256ShortMsg.rsp): the length in bits, the message and the hash
+    #[test]
+    fn bits() {
+        let vectors = [
+            (
+                0,
+                "00",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                1,
+                "00",
+                "bd4f9e98beb68c6ead3243b1b4c7fed75fa4feaab1f84795cbd8a98676a2a375",
+            ),
+            (
+                2,
+                "00",
+                "cb498bbdee020655704fb136e1bef3fe00dde8210a768a850b39324db1cc1de5",
+            ),
+            (
+                5,
+                "68",
+                "d6d3e02a31a84a8caa9718ed6c2057be09db45e7823eb5079ce7a573a3760f95",
+            ),
+            (
+                7,
+                "26",
+                "cd4281677ec888db079ef4420c8c09ee1ad29f41fdebf60a4524d202028c5176",
+            ),
+            (
+                9,
+                "2780",
+                "5d89267c7e940dc1a049f3d207dd3af5d3057c24c3c9c1bea334c53fd4fee9cc",
+            ),
+            (
+                15,
+                "2782",
+                "7fe7bf3336bb399b948c5078c1aa686630060ea3cd54d363c4fe3d71e2c810a3",
+            ),
+        ];
+        for (len, msg, md) in vectors {
+            let mut hasher = super::Sha256::new();
+            hasher.update_bits(&crate::test_util::hex(msg), len);
+            assert_eq!(
+                hasher.finalize()[..],
+                crate::test_util::hex(md),
+                "Len = {}",
+                len
+            );
+        }
+
+        // Lengths around the padding boundaries, with the unused bits of the last byte set
+        let data: Vec<u8> = (0..126)
+            .map(|i: u8| i.wrapping_mul(7).wrapping_add(3).wrapping_mul(13))
+            .collect();
+        let vectors = [
+            (
+                447,
+                "f98535cf1e88184f72e5e8c640f180701a299e403f9738dd1c2c9c69850e4164",
+            ),
+            (
+                448,
+                "da9823066668ed089b45827308e98479be94a3a5a9ea7170caf160aead6e1ee3",
+            ),
+            (
+                449,
+                "bc2d4d0297505d784a75eea89fffde60fe8e30e2bd2e02f9de0487405f884a33",
+            ),
+            (
+                511,
+                "7f088f475f8260464ac16219d7107af83e6a76069c89682e8a512e87430d81ca",
+            ),
+            (
+                513,
+                "20bc414a209af055350c09e3592289d0baa55e81a6fc68ee57bec4d128871be9",
+            ),
+            (
+                1001,
+                "a5b10302407df90e510db403060bec715b675ecafd57bb0e39685a9a68e02cd7",
+            ),
+        ];
+        for (len, md) in vectors {
+            let mut hasher = super::Sha256::new();
+            hasher.update_bits(&data, len);
+            assert_eq!(
+                hasher.finalize()[..],
+                crate::test_util::hex(md),
+                "Len = {}",
+                len
+            );
+        }
+
+        // Data after a partial byte continues right after its last bit: 101, "abc", 11111
+        let mut hasher = super::Sha256::new();
+        hasher.update_bits(&[0b10111111], 3);
+        Digest::update(&mut hasher, b"abc");
+        hasher.update_bits(&[0xff], 5);
+        assert_eq!(
+            hasher.finalize()[..],
+            crate::test_util::hex(
+                "6921923dcb8d00c26ddaf0e6c42c1c3abe85eb9c9857d4d849cd4047eaee8adc"
+            )
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use crate::Sha256;
+use digest::consts::U32;
+use digest::{Digest, FixedOutput, FixedOutputReset, HashMarker, Output, Reset, Update};
+
+/// Double SHA256, `SHA256(SHA256(x))`, the `hash256` of Bitcoin
+///
+/// Implements [`Digest`] through the traits of the `digest` crate, so it can be used like the
+/// other hashers:
+///
+/// ```
+/// use sha_crypto::{Digest, Sha256d};
+///
+/// let hash = Sha256d::digest(b"");
+/// assert_eq!(hash[..4], [0x5d, 0xf6, 0xe0, 0xe2]);
+/// ```
+#[derive(Clone)]
+pub struct Sha256d {
+    inner: Sha256,
+}
+
+impl Default for Sha256d {
+    fn default() -> Self {
+        Self {
+            inner: Sha256::new(),
+        }
+    }
+}
+
+impl HashMarker for Sha256d {}
+
+/// Defines the output size, the same as SHA256
+impl digest::OutputSizeUser for Sha256d {
+    type OutputSize = U32;
+}
+
+/// Hashes the message with the inner SHA256
+impl Update for Sha256d {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(&mut self.inner, data)
+    }
+}
+
+/// Hashes everything written, so the hasher can be the destination of `io::copy`
+#[cfg(feature = "std")]
+impl std::io::Write for Sha256d {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        std::io::Write::write(&mut self.inner, buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hash the hash of the message again
+impl FixedOutput for Sha256d {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        let hash = self.inner.finalize();
+        Digest::finalize_into(Sha256::new_with_prefix(hash), out)
+    }
+}
+
+/// Reset to an empty message
+impl Reset for Sha256d {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Output the hash and reset to an empty message
+impl FixedOutputReset for Sha256d {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        FixedOutput::finalize_into(core::mem::take(self), out)
+    }
+}
+
+/// BIP-340 tagged hashes, `SHA256(SHA256(tag) || SHA256(tag) || message)`
+///
+/// The prefix is exactly one block, so it is compressed once when the tag is given and every
+/// hash starts from the resulting state:
+///
+/// ```
+/// use sha_crypto::{Digest, TaggedHash};
+///
+/// let challenge = TaggedHash::new(b"BIP0340/challenge");
+/// let hash = challenge.hash(b"message");
+/// let same = challenge.hasher().chain_update(b"mess").chain_update(b"age").finalize();
+/// assert_eq!(hash, same);
+/// ```
+#[derive(Clone)]
+pub struct TaggedHash {
+    /// SHA256 after the prefix
+    prefixed: Sha256,
+}
+
+impl TaggedHash {
+    /// Hash the tag and compress the prefix
+    pub fn new(tag: &[u8]) -> Self {
+        let tag_hash = Sha256::digest(tag);
+        Self {
+            prefixed: Sha256::new().chain_update(tag_hash).chain_update(tag_hash),
+        }
+    }
+
+    /// A SHA256 hasher that has already hashed the prefix, to hash a message given in parts
+    pub fn hasher(&self) -> Sha256 {
+        self.prefixed.clone()
+    }
+
+    /// The tagged hash of a message
+    pub fn hash(&self, message: &[u8]) -> Output<Sha256> {
+        self.hasher().chain_update(message).finalize()
+    }
+}
+
+/// Tests for double SHA256 and tagged hashes
+#[cfg(test)]
+mod tests {
+    use super::{Sha256d, TaggedHash};
+    use crate::test_util::hex;
+    use digest::Digest;
+
+    // Double hashes match the known values and hashing the hash with sha2
+    #[test]
+    fn double() {
+        assert_eq!(
+            Sha256d::digest(b"")[..],
+            hex("5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456")
+        );
+        assert_eq!(
+            Sha256d::new()
+                .chain_update("hel")
+                .chain_update("lo")
+                .finalize()[..],
+            hex("9595c9df90075148eb06860365df33584b75bff782a510c6cd4883a419833d50")
+        );
+
+        let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let mut hasher = Sha256d::new();
+        for len in [0, 1, 55, 64, 200] {
+            let expected = sha2::Sha256::digest(sha2::Sha256::digest(&data[..len]));
+            hasher.update(&data[..len]);
+            assert_eq!(hasher.finalize_reset(), expected, "{} bytes", len);
+        }
+    }
+
+    // Tagged hashes match hashing the whole prefix, and start after one compression
+    #[test]
+    fn tagged() {
+        let challenge = TaggedHash::new(b"BIP0340/challenge");
+        assert_eq!(
+            challenge.hash(b"message")[..],
+            hex("f26c7f78a5e74cf7379558b9a0e390710b74d26ceaea5a5cbce3af592f35ecb1")
+        );
+        assert_eq!(
+            TaggedHash::new(b"TapLeaf").hash(b"")[..],
+            hex("5212c288a377d1f8164962a5a13429f9ba6a7b84e59776a52c6637df2106facb")
+        );
+
+        #[cfg(feature = "alloc")]
+        {
+            let state = challenge.hasher().state();
+            assert_eq!(state.length, 512);
+            assert!(state.buffer.is_empty());
+        }
+
+        let tag = sha2::Sha256::digest(b"a tag");
+        for len in [0, 1, 64, 100] {
+            let message = vec![0x42; len];
+            let expected = sha2::Sha256::new()
+                .chain_update(tag)
+                .chain_update(tag)
+                .chain_update(&message)
+                .finalize();
+            assert_eq!(TaggedHash::new(b"a tag").hash(&message), expected);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use crate::engine512::Engine512;
+use crate::sha512;
+#[cfg(feature = "alloc")]
+use crate::state::{HashState, InvalidState};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use digest::consts::U128;
+use digest::generic_array::ArrayLength;
+use digest::{
+    InvalidBufferSize, InvalidOutputSize, Reset, Update, VariableOutput, VariableOutputReset,
+};
+
+/// Check that SHA-512/t is defined for `t` bits of output
+///
+/// FIPS 180-4 allows any t below 512 except 384, which would be confused with SHA384
+fn check_bits(t: usize) -> Result<(), InvalidOutputSize> {
+    if t == 0 || t >= 512 || t == 384 {
+        Err(InvalidOutputSize)
+    } else {
+        Ok(())
+    }
+}
+
+/// The SHA-512/t IV generation function (FIPS 180-4 section 5.3.6)
+///
+/// Hashes the string "SHA-512/t" (with t in decimal) using a modified SHA512 IV, and uses the
+/// resulting hash value as the initial hash value of SHA-512/t. Assumes `t` is valid.
+///
+/// A const fn, so that the type-level sizes compute their IV once at compile time.
+const fn generate_iv(t: usize) -> [u64; 8] {
+    // The modified IV is the SHA512 IV with every byte xored with 0xa5
+    let mut modified = sha512::IV;
+    let mut i = 0;
+    while i < 8 {
+        modified[i] ^= 0xa5a5a5a5a5a5a5a5;
+        i += 1;
+    }
+    let mut engine = Engine512::new(modified);
+
+    // Write out t in decimal, it never has more than 3 digits
+    let mut digits = [0u8; 3];
+    let mut start = digits.len();
+    let mut rest = t;
+    while rest > 0 {
+        start -= 1;
+        digits[start] = b'0' + (rest % 10) as u8;
+        rest /= 10;
+    }
+    engine.update_const(b"SHA-512/");
+    engine.update_const(digits.split_at(start).1);
+
+    let mut out = [0u8; 64];
+    engine.finalize_const(&mut out);
+    let mut iv = [0u64; 8];
+    let mut rest = out.as_slice();
+    let mut i = 0;
+    while let Some((word, tail)) = rest.split_first_chunk::<8>() {
+        iv[i] = u64::from_be_bytes(*word);
+        rest = tail;
+        i += 1;
+    }
+    iv
+}
+
+/// SHA-512/t with the output size given at the type level, in bytes
+///
+/// For example `Sha512Trunc<U24>` is SHA-512/192. Using an output size SHA-512/t doesn't allow
+/// (0, 48 or more than 63 bytes) fails to compile:
+///
+/// ```compile_fail
+/// use sha_crypto::{digest::consts::U48, Digest, Sha512Trunc};
+///
+/// let hasher = Sha512Trunc::<U48>::new();
+/// ```
+#[derive(Clone)]
+pub struct Sha512Trunc<T> {
+    engine: Engine512,
+    size: PhantomData<T>,
+}
+
+impl<T: ArrayLength<u8>> Sha512Trunc<T> {
+    /// Evaluated when the hasher is created, to reject invalid output sizes at compile time
+    const VALID: () = assert!(
+        T::USIZE > 0 && T::USIZE < 64 && T::USIZE != 48,
+        "SHA-512/t requires 0 < t < 512 and t != 384"
+    );
+
+    /// The initial hash values for the output size, generated at compile time
+    const IV: [u64; 8] = {
+        let () = Self::VALID;
+        generate_iv(T::USIZE * 8)
+    };
+
+    /// Create the wrapper around an engine
+    fn with_engine(engine: Engine512) -> Self {
+        Self {
+            engine,
+            size: PhantomData,
+        }
+    }
+}
+
+impl_sha!([T: ArrayLength<u8>] Sha512Trunc<T>, Engine512, Self::IV, T, U128);
+
+/// SHA-512/t with the output size chosen at runtime, in bits
+///
+/// When t isn't a multiple of 8, the output is rounded up to whole bytes and the unused low bits
+/// of the last byte are set to zero.
+#[derive(Clone)]
+pub struct Sha512t {
+    engine: Engine512,
+    iv: [u64; 8],
+    bits: usize,
+}
+
+impl Sha512t {
+    /// Create a new SHA-512/t hasher with an output of `t_bits` bits
+    ///
+    /// Fails for t = 384, and t outside of 1..512
+    pub fn new(t_bits: usize) -> Result<Self, InvalidOutputSize> {
+        check_bits(t_bits)?;
+        let iv = generate_iv(t_bits);
+        Ok(Self {
+            engine: Engine512::new(iv),
+            iv,
+            bits: t_bits,
+        })
+    }
+
+    /// The size of the output, in bits
+    pub fn output_bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Hash `data`, or fail if the message gets too long, see [`Sha512::try_update`](crate::Sha512::try_update)
+    pub fn try_update(&mut self, data: &[u8]) -> Result<(), crate::LengthOverflow> {
+        self.engine.try_update(data)
+    }
+
+    /// Hash the first `bit_len` bits of `data`, see [`Sha512::update_bits`](crate::Sha512::update_bits)
+    pub fn update_bits(&mut self, data: &[u8], bit_len: usize) {
+        self.engine.update_bits(data, bit_len)
+    }
+
+    /// Export the internal state, to resume hashing later with [`Self::from_state`]
+    #[cfg(feature = "alloc")]
+    pub fn state(&self) -> HashState {
+        self.engine.to_state(self.bits as u16)
+    }
+
+    /// Restore a hasher from an exported state, with the output size the state was exported with
+    ///
+    /// Fails if the state belongs to another algorithm or isn't consistent
+    #[cfg(feature = "alloc")]
+    pub fn from_state(state: &HashState) -> Result<Self, InvalidState> {
+        let bits = state.output_bits as usize;
+        check_bits(bits).map_err(|_| InvalidState::WrongAlgorithm)?;
+        Ok(Self {
+            engine: Engine512::from_state(state, state.output_bits)?,
+            iv: generate_iv(bits),
+            bits,
+        })
+    }
+
+    /// Export the internal state in the versioned byte format of [`HashState`]
+    #[cfg(feature = "alloc")]
+    pub fn to_state_bytes(&self) -> Vec<u8> {
+        self.state().encode()
+    }
+
+    /// Restore a hasher from the bytes written by [`Self::to_state_bytes`]
+    #[cfg(feature = "alloc")]
+    pub fn from_state_bytes(bytes: &[u8]) -> Result<Self, InvalidState> {
+        Self::from_state(&HashState::from_bytes(bytes)?)
+    }
+}
+
+/// Serialised as its [`HashState`]
+#[cfg(feature = "serde")]
+impl serde::Serialize for Sha512t {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.state().serialize(serializer)
+    }
+}
+
+/// Deserialised from its [`HashState`], which is validated
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Sha512t {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = HashState::deserialize(deserializer)?;
+        Self::from_state(&state).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Updates the struct variables for the hash
+///
+/// Panics if the message gets longer than the algorithm allows, see [`Sha512t::try_update`].
+impl Update for Sha512t {
+    fn update(&mut self, data: &[u8]) {
+        self.engine.update(data)
+    }
+}
+
+/// Hashes everything written, so the hasher can be the destination of `io::copy`
+///
+/// Writing past the longest message the algorithm allows fails with `InvalidInput`.
+#[cfg(feature = "std")]
+impl std::io::Write for Sha512t {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.engine
+            .try_update(buf)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reset the hasher, keeping the output size
+impl Reset for Sha512t {
+    fn reset(&mut self) {
+        self.engine = Engine512::new(self.iv);
+    }
+}
+
+/// Interoperability with the variable output traits
+///
+/// Note that `VariableOutput::new` takes the output size in bytes, unlike [`Sha512t::new`]
+impl VariableOutput for Sha512t {
+    const MAX_OUTPUT_SIZE: usize = 63;
+
+    fn new(output_size: usize) -> Result<Self, InvalidOutputSize> {
+        // Resolves to the inherent constructor, which takes bits
+        Sha512t::new(output_size.checked_mul(8).ok_or(InvalidOutputSize)?)
+    }
+
+    fn output_size(&self) -> usize {
+        self.bits.div_ceil(8)
+    }
+
+    fn finalize_variable(self, out: &mut [u8]) -> Result<(), InvalidBufferSize> {
+        if out.len() != self.output_size() {
+            return Err(InvalidBufferSize);
+        }
+        let bits = self.bits;
+        self.engine.finalize_into(out);
+
+        // Clear the bits past t in the last byte
+        if !bits.is_multiple_of(8) {
+            out[bits / 8] &= 0xff << (8 - bits % 8);
+        }
+        Ok(())
+    }
+}
+
+/// Reset our algorithm's output
+impl VariableOutputReset for Sha512t {
+    fn finalize_variable_reset(&mut self, out: &mut [u8]) -> Result<(), InvalidBufferSize> {
+        let fresh = Self {
+            engine: Engine512::new(self.iv),
+            ..*self
+        };
+        core::mem::replace(self, fresh).finalize_variable(out)
+    }
+}
+
+/// Tests for our SHA-512/t implementation
+#[cfg(test)]
+mod tests {
+    use crate::test_util::hex;
+    use digest::consts::{U28, U32};
+    use digest::*;
+
+    // The IV generation function reproduces the IVs published for SHA-512/224 and SHA-512/256
+    #[test]
+    fn generated_iv() {
+        assert_eq!(super::generate_iv(224), crate::sha512::IV_224);
+        assert_eq!(super::generate_iv(256), crate::sha512::IV_256);
+    }
+
+    // The standard truncations match Rust's SHA-512/224 and SHA-512/256 algorithms
+    #[test]
+    fn standard_sizes() {
+        for s in ["", "hello world", "Trying some numbers: 1234"] {
+            let my_res = super::Sha512Trunc::<U28>::new().chain_update(s).finalize();
+            let ex_res = sha2::Sha512_224::new().chain_update(s).finalize();
+            assert_eq!(ex_res, my_res, "Failed to hash `{}` correctly", s);
+            let my_res = super::Sha512Trunc::<U32>::new().chain_update(s).finalize();
+            let ex_res = sha2::Sha512_256::new().chain_update(s).finalize();
+            assert_eq!(ex_res, my_res, "Failed to hash `{}` correctly", s);
+
+            let mut hasher = super::Sha512t::new(256).unwrap();
+            hasher.update(s.as_bytes());
+            let mut my_res = [0u8; 32];
+            hasher.finalize_variable(&mut my_res).unwrap();
+            assert_eq!(ex_res[..], my_res, "Failed to hash `{}` correctly", s);
+        }
+    }
+
+    // Other output sizes, checked against an independent implementation
+    #[test]
+    fn other_sizes() {
+        let expected = hex("b0bec068cb45091f859b9eb60a442c4f7f2d94b40bb28064");
+        let my_res = super::Sha512Trunc::<consts::U24>::digest("hello world");
+        assert_eq!(expected, my_res[..]);
+
+        for (t, data, expected) in [
+            (
+                192,
+                "hello world",
+                "b0bec068cb45091f859b9eb60a442c4f7f2d94b40bb28064",
+            ),
+            (12, "hello world", "b970"),
+            (1, "", "80"),
+        ] {
+            let mut hasher = super::Sha512t::new(t).unwrap();
+            hasher.update(data.as_bytes());
+            let mut my_res = vec![0u8; hasher.output_size()];
+            hasher.finalize_variable(&mut my_res).unwrap();
+            assert_eq!(hex(expected), my_res, "Failed SHA-512/{}", t);
+        }
+    }
+
+    // Output sizes that SHA-512/t doesn't define are rejected
+    #[test]
+    fn invalid_sizes() {
+        for t in [0, 384, 512, 1024] {
+            assert!(super::Sha512t::new(t).is_err(), "Accepted SHA-512/{}", t);
+        }
+        // In bytes, the size in bits would wrap around to 256 without the overflow check
+        for size in [48, usize::MAX / 8 + 33] {
+            let hasher = <super::Sha512t as VariableOutput>::new(size);
+            assert!(hasher.is_err(), "Accepted {} bytes", size);
+        }
+        let mut hasher = super::Sha512t::new(256).unwrap();
+        assert!(hasher.finalize_variable_reset(&mut [0u8; 28]).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+//! SHA-crypt password hashes, as found in `/etc/shadow`
+//!
+//! Implements Ulrich Drepper's "Unix crypt using SHA-256 and SHA-512" specification, which
+//! produces modular crypt format strings such as `$5$rounds=10000$salt$hash` (SHA256) and
+//! `$6$salt$hash` (SHA512).
+//!
+//! ```
+//! use sha_crypto::{sha_crypt, Sha256};
+//!
+//! let hash = sha_crypt::hash::<Sha256>(b"Hello world!", "saltstring", sha_crypt::DEFAULT_ROUNDS);
+//! assert_eq!(hash, "$5$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5");
+//! assert!(sha_crypt::verify(b"Hello world!", &hash));
+//! ```
+
+use crate::{Sha256, Sha512};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use digest::Digest;
+
+/// The number of rounds used when the hash doesn't specify one
+pub const DEFAULT_ROUNDS: u32 = 5000;
+/// Fewer rounds are raised to this
+pub const MIN_ROUNDS: u32 = 1000;
+/// More rounds are lowered to this
+pub const MAX_ROUNDS: u32 = 999_999_999;
+/// Longer salts are truncated to this many bytes
+pub const MAX_SALT_LEN: usize = 16;
+
+/// The base64 alphabet used by crypt, which differs from the usual one
+const ALPHABET: &[u8; 64] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for crate::Sha256 {}
+    impl Sealed for crate::Sha512 {}
+}
+
+/// The hashers SHA-crypt is defined for, [`Sha256`] and [`Sha512`]
+pub trait ShaCryptHash: Digest + Clone + sealed::Sealed {
+    /// The identifier at the start of the hash string
+    const PREFIX: &'static str;
+    /// The order the bytes of the hash are encoded in, in groups of three
+    const ENCODING_ORDER: &'static [u8];
+}
+
+impl ShaCryptHash for Sha256 {
+    const PREFIX: &'static str = "$5$";
+    const ENCODING_ORDER: &'static [u8] = &[
+        0, 10, 20, 21, 1, 11, 12, 22, 2, 3, 13, 23, 24, 4, 14, 15, 25, 5, 6, 16, 26, 27, 7, 17, 18,
+        28, 8, 9, 19, 29, 31, 30,
+    ];
+}
+
+impl ShaCryptHash for Sha512 {
+    const PREFIX: &'static str = "$6$";
+    const ENCODING_ORDER: &'static [u8] = &[
+        0, 21, 42, 22, 43, 1, 44, 2, 23, 3, 24, 45, 25, 46, 4, 47, 5, 26, 6, 27, 48, 28, 49, 7, 50,
+        8, 29, 9, 30, 51, 31, 52, 10, 53, 11, 32, 12, 33, 54, 34, 55, 13, 56, 14, 35, 15, 36, 57,
+        37, 58, 16, 59, 17, 38, 18, 39, 60, 40, 61, 19, 62, 20, 41, 63,
+    ];
+}
+
+/// Hash a password, returning the full hash string
+///
+/// The salt is cut at the first `$` and truncated to [`MAX_SALT_LEN`] bytes, and the rounds are
+/// clamped between [`MIN_ROUNDS`] and [`MAX_ROUNDS`]. The rounds are only written out when they
+/// differ from [`DEFAULT_ROUNDS`].
+///
+/// When the 16th byte of the salt falls inside a multi-byte character, the hash is the one glibc
+/// computes, but the hash string keeps that whole character in the salt.
+pub fn hash<D: ShaCryptHash>(password: &[u8], salt: &str, rounds: u32) -> String {
+    let (salt, hashed_salt) = truncate_salt(salt);
+    let mut out = String::from(D::PREFIX);
+    if rounds != DEFAULT_ROUNDS {
+        write!(out, "rounds={}$", rounds.clamp(MIN_ROUNDS, MAX_ROUNDS)).unwrap();
+    }
+    out.push_str(salt);
+    out.push('$');
+    encode(
+        &sha_crypt::<D>(password, hashed_salt, rounds),
+        D::ENCODING_ORDER,
+        &mut out,
+    );
+    out
+}
+
+/// Check a password against a `$5$` or `$6$` hash string
+///
+/// Returns false for hash strings that can't be parsed. The hashes are compared in constant time.
+pub fn verify(password: &[u8], hash: &str) -> bool {
+    if let Some(rest) = hash.strip_prefix(Sha256::PREFIX) {
+        verify_with::<Sha256>(password, rest).unwrap_or(false)
+    } else if let Some(rest) = hash.strip_prefix(Sha512::PREFIX) {
+        verify_with::<Sha512>(password, rest).unwrap_or(false)
+    } else {
+        false
+    }
+}
+
+/// Parse the rest of the hash string after the prefix, and check the password against it
+fn verify_with<D: ShaCryptHash>(password: &[u8], rest: &str) -> Option<bool> {
+    let (rounds, rest) = match rest.strip_prefix("rounds=") {
+        Some(rest) => {
+            let (rounds, rest) = rest.split_once('$')?;
+            let rounds = rounds.parse::<u64>().ok()?.min(MAX_ROUNDS as u64) as u32;
+            (rounds, rest)
+        }
+        None => (DEFAULT_ROUNDS, rest),
+    };
+    let (salt, expected) = rest.split_once('$')?;
+    let (_, salt) = truncate_salt(salt);
+
+    let mut computed = String::new();
+    encode(
+        &sha_crypt::<D>(password, salt, rounds),
+        D::ENCODING_ORDER,
+        &mut computed,
+    );
+    if computed.len() != expected.len() {
+        return Some(false);
+    }
+    let diff = computed
+        .bytes()
+        .zip(expected.bytes())
+        .fold(0, |diff, (a, b)| diff | (a ^ b));
+    Some(diff == 0)
+}
+
+/// Cut the salt at the first `$`, and truncate it to the [`MAX_SALT_LEN`] bytes that are hashed
+///
+/// Returns the salt to write in the hash string and the bytes to hash. Like glibc, the first 16
+/// bytes are hashed even when they end inside a multi-byte character. The written salt then keeps
+/// the whole character so the string stays valid UTF-8, and truncating it again when verifying
+/// gives back the same bytes.
+fn truncate_salt(salt: &str) -> (&str, &[u8]) {
+    let salt = salt.split('$').next().unwrap_or_default();
+    let hashed = &salt.as_bytes()[..salt.len().min(MAX_SALT_LEN)];
+    let mut len = hashed.len();
+    while !salt.is_char_boundary(len) {
+        len += 1;
+    }
+    (&salt[..len], hashed)
+}
+
+/// The SHA-crypt algorithm itself, returning the raw hash
+fn sha_crypt<D: ShaCryptHash>(password: &[u8], salt: &[u8], rounds: u32) -> digest::Output<D> {
+    let rounds = rounds.clamp(MIN_ROUNDS, MAX_ROUNDS);
+    let hash_len = <D as Digest>::output_size();
+
+    // Digest B is the password, salt and password again
+    let b = D::new()
+        .chain_update(password)
+        .chain_update(salt)
+        .chain_update(password)
+        .finalize();
+
+    // Digest A is the password and salt, followed by as much of B as the password is long
+    let mut a = D::new().chain_update(password).chain_update(salt);
+    let mut rest = password.len();
+    while rest > hash_len {
+        a.update(&b);
+        rest -= hash_len;
+    }
+    a.update(&b[..rest]);
+
+    // Then for every bit of the password length, B if it is set or the password if it isn't
+    let mut bits = password.len();
+    while bits > 0 {
+        if bits & 1 != 0 {
+            a.update(&b);
+        } else {
+            a.update(password);
+        }
+        bits >>= 1;
+    }
+    let mut a = a.finalize();
+
+    // Sequence P is made from the password hashed once for every byte of it
+    let mut dp = D::new();
+    for _ in 0..password.len() {
+        dp.update(password);
+    }
+    let dp = dp.finalize();
+    let p: Vec<u8> = dp.iter().cycle().take(password.len()).copied().collect();
+
+    // Sequence S is made from the salt hashed 16 + A[0] times
+    let mut ds = D::new();
+    for _ in 0..16 + a[0] as usize {
+        ds.update(salt);
+    }
+    let ds = ds.finalize();
+    let s: Vec<u8> = ds.iter().cycle().take(salt.len()).copied().collect();
+
+    // Each round mixes the previous hash with P and S, depending on the round number
+    for i in 0..rounds {
+        let mut c = D::new();
+        if i % 2 == 1 {
+            c.update(&p);
+        } else {
+            c.update(&a);
+        }
+        if i % 3 != 0 {
+            c.update(&s);
+        }
+        if i % 7 != 0 {
+            c.update(&p);
+        }
+        if i % 2 == 1 {
+            c.update(&a);
+        } else {
+            c.update(&p);
+        }
+        a = c.finalize();
+    }
+    a
+}
+
+/// Encode the hash with the crypt base64 alphabet, in the order the algorithm requires
+///
+/// Every group of three bytes is read as a big endian number, and written out 6 bits at a time
+/// starting from the least significant bits.
+fn encode(hash: &[u8], order: &[u8], out: &mut String) {
+    for group in order.chunks(3) {
+        let mut w = group
+            .iter()
+            .fold(0u32, |w, &i| (w << 8) | hash[i as usize] as u32);
+        for _ in 0..(group.len() * 8).div_ceil(6) {
+            out.push(ALPHABET[(w & 0x3f) as usize] as char);
+            w >>= 6;
+        }
+    }
+}
+
+/// Tests for our SHA-crypt implementation
+#[cfg(test)]
+mod tests {
+    use super::{hash, verify};
+    use crate::{Sha256, Sha512};
+
+    // Parse the salt and rounds from the expected hash like crypt would, hash the password with
+    // them and check the output. Then check that the password verifies against the hash.
+    macro_rules! sha_crypt_test {
+        ($hash:ty, $password:literal, $expected:literal) => {
+            let rest = &$expected[3..];
+            let (rounds, rest) = match rest.strip_prefix("rounds=") {
+                Some(rest) => {
+                    let (rounds, rest) = rest.split_once('$').unwrap();
+                    (rounds.parse().unwrap(), rest)
+                }
+                None => (super::DEFAULT_ROUNDS, rest),
+            };
+            assert_eq!(hash::<$hash>($password, rest, rounds), $expected);
+            assert!(verify($password, $expected), "Failed to verify {}", $expected);
+        };
+    }
+
+    // The SHA256 test vectors from the specification
+    #[test]
+    fn sha256_spec() {
+        sha_crypt_test!(
+            Sha256,
+            b"Hello world!",
+            "$5$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5"
+        );
+        sha_crypt_test!(
+            Sha256,
+            b"Hello world!",
+            "$5$rounds=10000$saltstringsaltst$3xv.VbSHBb41AL9AvLeujZkZRBAwqFMz2.opqey6IcA"
+        );
+        sha_crypt_test!(
+            Sha256,
+            b"a very much longer text to encrypt.  This one even stretches over morethan one line.",
+            "$5$rounds=1400$anotherlongsalts$Rx.j8H.h8HjEDGomFU8bDkXm3XIUnzyxf12oP84Bnq1"
+        );
+        sha_crypt_test!(
+            Sha256,
+            b"we have a short salt string but not a short password",
+            "$5$rounds=77777$short$JiO1O3ZpDAxGJeaDIuqCoEFysAe1mZNJRs3pw0KQRd/"
+        );
+        sha_crypt_test!(
+            Sha256,
+            b"a short string",
+            "$5$rounds=123456$asaltof16chars..$gP3VQ/6X7UUEW3HkBn2w1/Ptq2jxPyzV/cZKmF/wJvD"
+        );
+        sha_crypt_test!(
+            Sha256,
+            b"the minimum number is still observed",
+            "$5$rounds=1000$roundstoolow$yfvwcWrQ8l/K0DAWyuPMDNHpIVlTQebY9l/gL972bIC"
+        );
+    }
+
+    // The SHA512 test vectors from the specification
+    #[test]
+    fn sha512_spec() {
+        sha_crypt_test!(
+            Sha512,
+            b"Hello world!",
+            "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJu\
+             esI68u4OTLiBFdcbYEdFCoEOfaS35inz1"
+        );
+        sha_crypt_test!(
+            Sha512,
+            b"Hello world!",
+            "$6$rounds=10000$saltstringsaltst$OW1/O6BYHV6BcXZu8QVeXbDWra3Oeqh0sbHbbMCVNSnCM/\
+             UrjmM0Dp8vOuZeHBy/YTBmSK6H9qs/y3RnOaw5v."
+        );
+        sha_crypt_test!(
+            Sha512,
+            b"a very much longer text to encrypt.  This one even stretches over morethan one line.",
+            "$6$rounds=1400$anotherlongsalts$POfYwTEok97VWcjxIiSOjiykti.o/pQs.wPvMxQ6Fm7I6IoYN3\
+             CmLs66x9t0oSwbtEW7o7UmJEiDwGqd8p4ur1"
+        );
+        sha_crypt_test!(
+            Sha512,
+            b"we have a short salt string but not a short password",
+            "$6$rounds=77777$short$WuQyW2YR.hBNpjjRhpYD/ifIw05xdfeEyQoMxIXbkvr0gge1a1x3yRULJ5\
+             CCaUeOxFmtlcGZelFl5CxtgfiAc0"
+        );
+        sha_crypt_test!(
+            Sha512,
+            b"a short string",
+            "$6$rounds=123456$asaltof16chars..$BtCwjqMJGx5hrJhZywWvt0RLE8uZ4oPwcelCjmw2kSYu.\
+             Ec6ycULevoBK25fs2xXgMNrCzIMVcgEJAstJeonj1"
+        );
+        sha_crypt_test!(
+            Sha512,
+            b"the minimum number is still observed",
+            "$6$rounds=1000$roundstoolow$kUMsbe306n21p9R.FRkW3IGn.S9NPN0x50YhH1xhLsPuWGsUSklZt5\
+             8jaTfF4ZEQpyUNGc0dqbpBYYBaHHrsX."
+        );
+    }
+
+    // Long salts are truncated, too few rounds are raised to the minimum, and hashes that were
+    // written with too few rounds or too long salts still verify
+    #[test]
+    fn clamping() {
+        assert_eq!(
+            hash::<Sha256>(b"This is just a test", "toolongsaltstring", 5001),
+            hash::<Sha256>(b"This is just a test", "toolongsaltstrin", 5001),
+        );
+        assert_eq!(
+            hash::<Sha256>(b"the minimum number is still observed", "roundstoolow", 10),
+            "$5$rounds=1000$roundstoolow$yfvwcWrQ8l/K0DAWyuPMDNHpIVlTQebY9l/gL972bIC"
+        );
+        assert!(verify(
+            b"the minimum number is still observed",
+            "$5$rounds=10$roundstoolow$yfvwcWrQ8l/K0DAWyuPMDNHpIVlTQebY9l/gL972bIC"
+        ));
+        assert!(verify(
+            b"This is just a test",
+            "$5$rounds=5000$toolongsaltstring$Un/5jzAHMgOGZ5.mWJpuVolil07guHPvOW8mGRcvxa5"
+        ));
+
+        // Salts are truncated to 16 bytes like glibc does, even inside a character
+        let hash_256 = "$5$aéééééééé$a9Pl/DU2wm4W6hv/L4zAAlb4DR6MPPDRB05mF04jWe2";
+        assert_eq!(hash::<Sha256>(b"password", "aéééééééé", 5000), hash_256);
+        assert!(verify(b"password", hash_256));
+        assert_eq!(
+            hash::<Sha512>(b"password", "aéééééééé", 5000),
+            "$6$aéééééééé$LZUePiZAM6BB2hPU0EKrg9HLDRCSBSUc.aSPk5yX/wKg73crau0R38uqkvCBhDEfJWmTL5J\
+             ru0RZuAd8oxa2c."
+        );
+        assert!(!verify(
+            b"password",
+            "$5$aééééééé$a9Pl/DU2wm4W6hv/L4zAAlb4DR6MPPDRB05mF04jWe2"
+        ));
+    }
+
+    // Wrong passwords and malformed hashes don't verify
+    #[test]
+    fn rejects() {
+        let hash = "$5$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5";
+        assert!(!verify(b"Hello world", hash));
+        assert!(!verify(b"Hello world!", &hash[..hash.len() - 1]));
+        assert!(!verify(b"Hello world!", &hash.replace("$5$", "$6$")));
+        assert!(!verify(b"Hello world!", "$5$saltstring"));
+        assert!(!verify(
+            b"Hello world!",
+            "$5$rounds=abc$saltstring$5B8vYYiY"
+        ));
+        assert!(!verify(b"Hello world!", "$1$saltstring$5B8vYYiY"));
+    }
+}
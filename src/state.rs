@@ -0,0 +1,372 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The version of the format written by [`HashState::to_bytes`]
+pub const STATE_VERSION: u8 = 1;
+
+/// A snapshot of a hasher's internal state, to resume hashing later or on another machine
+///
+/// Every hasher can export its state with `state()` or `to_state_bytes()`, and be restored with
+/// `from_state()` or `from_state_bytes()`, which check that the state belongs to the same
+/// algorithm and is consistent.
+///
+/// The byte format starts with the version, followed by the word size in bits, the output size in
+/// bits (2 bytes), the 8 words of the hash value, the message length in bits (a word of twice the
+/// word size), the number of buffered bytes and the buffered bytes themselves. Every number is
+/// big endian.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HashState {
+    /// The size of the words of the compression function in bits, 32 or 64
+    pub word_bits: u8,
+    /// The output size of the algorithm in bits
+    pub output_bits: u16,
+    /// The intermediate hash value, 32-bit words only use the low half
+    pub h: [u64; 8],
+    /// The length of the message hashed so far, in bits
+    pub length: u128,
+    /// The bytes of the incomplete block that haven't been compressed yet, the last one partial
+    /// when the length isn't a whole number of bytes
+    pub buffer: Vec<u8>,
+}
+
+/// Errors when restoring a hasher from a [`HashState`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidState {
+    /// The state was written by an unknown version of the format
+    UnsupportedVersion(u8),
+    /// The state belongs to a different algorithm
+    WrongAlgorithm,
+    /// The buffer doesn't fit in a block, or doesn't match the message length
+    InvalidBuffer,
+    /// A word or the message length is out of range for the algorithm
+    OutOfRange,
+    /// The state bytes are too short or too long
+    InvalidLength,
+}
+
+impl fmt::Display for InvalidState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedVersion(v) => write!(f, "unsupported hash state version {}", v),
+            Self::WrongAlgorithm => write!(f, "hash state belongs to a different algorithm"),
+            Self::InvalidBuffer => write!(f, "hash state has an invalid buffer"),
+            Self::OutOfRange => write!(f, "hash state has out of range values"),
+            Self::InvalidLength => write!(f, "hash state has the wrong length"),
+        }
+    }
+}
+
+impl core::error::Error for InvalidState {}
+
+impl HashState {
+    /// Serialise the state into the versioned byte format
+    ///
+    /// Fails if the word size isn't 32 or 64 bits, or if a word, the length or the buffer doesn't
+    /// fit in the format. The states exported by the hashers always fit.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, InvalidState> {
+        if self.word_bits != 32 && self.word_bits != 64 {
+            return Err(InvalidState::WrongAlgorithm);
+        }
+        let word_max = u64::MAX >> (64 - self.word_bits);
+        let length_max = u128::MAX >> (128 - 2 * self.word_bits as u32);
+        if self.h.iter().any(|&h| h > word_max) || self.length > length_max {
+            return Err(InvalidState::OutOfRange);
+        }
+        // The buffer never holds more than a block of 16 words
+        if self.buffer.len() > 2 * self.word_bits as usize {
+            return Err(InvalidState::InvalidBuffer);
+        }
+        Ok(self.encode())
+    }
+
+    /// Serialise a state that is known to fit in the format
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let word_bytes = self.word_bits as usize / 8;
+        let mut out = vec![STATE_VERSION, self.word_bits];
+        out.extend_from_slice(&self.output_bits.to_be_bytes());
+        for h in self.h {
+            out.extend_from_slice(&h.to_be_bytes()[8 - word_bytes..]);
+        }
+        out.extend_from_slice(&self.length.to_be_bytes()[16 - 2 * word_bytes..]);
+        out.push(self.buffer.len() as u8);
+        out.extend_from_slice(&self.buffer);
+        out
+    }
+
+    /// Parse a state from the versioned byte format
+    ///
+    /// This only checks the format itself, the hashers check that the contents make sense.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidState> {
+        let (&version, rest) = bytes.split_first().ok_or(InvalidState::InvalidLength)?;
+        if version != STATE_VERSION {
+            return Err(InvalidState::UnsupportedVersion(version));
+        }
+        let (&word_bits, rest) = rest.split_first().ok_or(InvalidState::InvalidLength)?;
+        if word_bits != 32 && word_bits != 64 {
+            return Err(InvalidState::WrongAlgorithm);
+        }
+        let word_bytes = word_bits as usize / 8;
+
+        // Everything up to the buffer has a fixed size
+        let fixed = 2 + 8 * word_bytes + 2 * word_bytes + 1;
+        if rest.len() < fixed {
+            return Err(InvalidState::InvalidLength);
+        }
+        let (fixed, buffer) = rest.split_at(fixed);
+        let (output_bits, fixed) = fixed.split_at(2);
+        let (h_bytes, fixed) = fixed.split_at(8 * word_bytes);
+        let (length, filled) = fixed.split_at(2 * word_bytes);
+        if buffer.len() != filled[0] as usize {
+            return Err(InvalidState::InvalidLength);
+        }
+
+        let mut h = [0u64; 8];
+        h_bytes
+            .chunks(word_bytes)
+            .zip(h.iter_mut())
+            .for_each(|(buf, h)| *h = read_be(buf) as u64);
+        Ok(Self {
+            word_bits,
+            output_bits: u16::from_be_bytes([output_bits[0], output_bits[1]]),
+            h,
+            length: read_be(length),
+            buffer: buffer.to_vec(),
+        })
+    }
+}
+
+/// Read a big endian number of up to 16 bytes
+fn read_be(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(0, |n, &b| (n << 8) | b as u128)
+}
+
+/// Tests for exporting and restoring hasher states
+#[cfg(test)]
+mod tests {
+    use super::{HashState, InvalidState, STATE_VERSION};
+    use crate::test_util::hex;
+    use crate::{LengthOverflow, Sha224, Sha256, Sha384, Sha512, Sha512Trunc, Sha512_224, Sha512t};
+    use digest::consts::U24;
+    use digest::{Digest, Update, VariableOutput};
+
+    // Hash part of the message, move the state through its bytes into a new hasher, and finish
+    // hashing there. The result should be the same as hashing in one go.
+    macro_rules! resume_test {
+        ($hash:ty) => {
+            let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+            for split in [0, 1, 63, 64, 65, 127, 128, 129, 300] {
+                let first = <$hash>::new().chain_update(&data[..split]);
+                let resumed = <$hash>::from_state_bytes(&first.to_state_bytes())
+                    .unwrap()
+                    .chain_update(&data[split..])
+                    .finalize();
+                assert_eq!(
+                    <$hash>::digest(&data),
+                    resumed,
+                    "Failed to resume after {} bytes",
+                    split
+                );
+            }
+        };
+    }
+
+    #[test]
+    fn resume() {
+        resume_test!(Sha224);
+        resume_test!(Sha256);
+        resume_test!(Sha384);
+        resume_test!(Sha512);
+        resume_test!(Sha512_224);
+        resume_test!(Sha512Trunc<U24>);
+    }
+
+    #[test]
+    fn resume_runtime_size() {
+        let mut first = Sha512t::new(200).unwrap();
+        first.update(b"hello ");
+        let mut resumed = Sha512t::from_state_bytes(&first.to_state_bytes()).unwrap();
+        resumed.update(b"world");
+        let mut expected = Sha512t::new(200).unwrap();
+        expected.update(b"hello world");
+        assert_eq!(
+            expected.finalize_boxed(),
+            resumed.finalize_boxed(),
+            "Failed to resume SHA-512/200"
+        );
+    }
+
+    // Resuming in the middle of a byte continues with the next bit
+    #[test]
+    fn resume_bits() {
+        let mut expected = Sha256::new();
+        expected.update_bits(&[0x5a, 0xc3], 13);
+        let mut first = Sha256::new();
+        first.update_bits(&[0x5a, 0xc3], 5);
+        let state = first.state();
+        assert_eq!((state.length, state.buffer.as_slice()), (5, &[0x58][..]));
+        let mut resumed = Sha256::from_state_bytes(&first.to_state_bytes()).unwrap();
+        resumed.update_bits(&[0x58, 0x00], 8);
+        assert_eq!(expected.finalize(), resumed.finalize());
+
+        // A partial byte needs its unused bits clear, and the length must match the buffer
+        let mut unused_bits = state.clone();
+        unused_bits.buffer[0] |= 1;
+        assert_eq!(
+            Sha256::from_state(&unused_bits).err(),
+            Some(InvalidState::InvalidBuffer)
+        );
+        let mut long_length = state;
+        long_length.length = 9;
+        assert_eq!(
+            Sha256::from_state(&long_length).err(),
+            Some(InvalidState::InvalidBuffer)
+        );
+    }
+
+    // The byte format is the documented one
+    #[test]
+    fn format() {
+        let hasher = Sha256::new().chain_update(b"abc");
+        let bytes = hasher.to_state_bytes();
+        assert_eq!(bytes.len(), 1 + 1 + 2 + 8 * 4 + 8 + 1 + 3);
+        assert_eq!(bytes[..4], [STATE_VERSION, 32, 0x01, 0x00]);
+        assert_eq!(bytes[4..8], [0x6a, 0x09, 0xe6, 0x67]);
+        assert_eq!(bytes[36..], [0, 0, 0, 0, 0, 0, 0, 24, 3, b'a', b'b', b'c']);
+        assert_eq!(HashState::from_bytes(&bytes).unwrap(), hasher.state());
+        assert_eq!(hasher.state().to_bytes(), Ok(bytes));
+    }
+
+    // States built by hand that don't fit in the format can't be serialised
+    #[test]
+    fn unserialisable() {
+        let state = Sha256::new().chain_update(b"abc").state();
+        let mut wide_words = state.clone();
+        wide_words.word_bits = 128;
+        assert_eq!(wide_words.to_bytes(), Err(InvalidState::WrongAlgorithm));
+        let mut long_buffer = state.clone();
+        long_buffer.buffer = vec![0; 300];
+        assert_eq!(long_buffer.to_bytes(), Err(InvalidState::InvalidBuffer));
+        let mut wide_word = state.clone();
+        wide_word.h[0] = 1 << 32;
+        assert_eq!(wide_word.to_bytes(), Err(InvalidState::OutOfRange));
+        let mut long_length = state;
+        long_length.length = 1 << 64;
+        assert_eq!(long_length.to_bytes(), Err(InvalidState::OutOfRange));
+    }
+
+    // States that don't belong to the algorithm or aren't consistent are rejected
+    #[test]
+    fn invalid() {
+        let bytes = Sha256::new().chain_update(b"abc").to_state_bytes();
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 2;
+        assert_eq!(
+            Sha256::from_state_bytes(&wrong_version).err(),
+            Some(InvalidState::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            Sha224::from_state_bytes(&bytes).err(),
+            Some(InvalidState::WrongAlgorithm)
+        );
+        assert_eq!(
+            Sha512::from_state_bytes(&bytes).err(),
+            Some(InvalidState::WrongAlgorithm)
+        );
+        assert_eq!(
+            Sha256::from_state_bytes(&bytes[..bytes.len() - 1]).err(),
+            Some(InvalidState::InvalidLength)
+        );
+        assert_eq!(
+            Sha256::from_state_bytes(&[bytes.as_slice(), &[0]].concat()).err(),
+            Some(InvalidState::InvalidLength)
+        );
+
+        let state = Sha256::new().chain_update(b"abc").state();
+        let mut full_buffer = state.clone();
+        full_buffer.buffer = vec![0; 64];
+        full_buffer.length = 64 * 8;
+        assert_eq!(
+            Sha256::from_state(&full_buffer).err(),
+            Some(InvalidState::InvalidBuffer)
+        );
+        let mut wrong_length = state.clone();
+        wrong_length.length = 4 * 8;
+        assert_eq!(
+            Sha256::from_state(&wrong_length).err(),
+            Some(InvalidState::InvalidBuffer)
+        );
+        let mut wide_word = state;
+        wide_word.h[3] = 1 << 32;
+        assert_eq!(
+            Sha256::from_state(&wide_word).err(),
+            Some(InvalidState::OutOfRange)
+        );
+    }
+
+    // Starting a few bits short of the longest message, updates past the limit fail and leave
+    // the hasher as it was, and the message can still be completed and finalised
+    #[test]
+    fn length_limit() {
+        let mut state = Sha256::new().chain_update([0x5a; 63]).state();
+        state.length = u64::MAX as u128 - 7;
+        let mut hasher = Sha256::from_state(&state).unwrap();
+        assert_eq!(hasher.try_update(&[0]), Err(LengthOverflow));
+        assert_eq!(hasher.try_update(&[]), Ok(()));
+        assert_eq!(hasher.state(), state);
+        hasher.update_bits(&[0xff], 7);
+        assert_eq!(hasher.try_update(&[0]), Err(LengthOverflow));
+        assert_eq!(hasher.state().length, u64::MAX as u128);
+        assert_eq!(
+            hasher.finalize()[..],
+            hex("4483296db7710f5f485ae1a81f1762dce58bf15cf5520dbb2e1c8bae8068d3d4")
+        );
+
+        let mut state = Sha512::new().chain_update([0x5a; 127]).state();
+        state.length = u128::MAX - 7;
+        let mut hasher = Sha512::from_state(&state).unwrap();
+        assert_eq!(hasher.try_update(&[0]), Err(LengthOverflow));
+        assert_eq!(hasher.state(), state);
+        hasher.update_bits(&[0xff], 7);
+        assert_eq!(hasher.state().length, u128::MAX);
+        assert_eq!(
+            hasher.finalize()[..],
+            hex(concat!(
+                "84bbab85acc8fa928e4d68c77c8d6f7e38173d14e999157a164a01b7fb3a8ab1",
+                "374c7493d59f3e623053a24e28be70d529b900d84df6e937b818143c4b4c11c0"
+            ))
+        );
+    }
+
+    // Past the limit, `update` panics and writing fails rather than hashing a wrong length
+    #[test]
+    fn length_overflow() {
+        let mut state = Sha224::new().state();
+        state.length = u64::MAX as u128 - 511;
+        let hasher = Sha224::from_state(&state).unwrap();
+
+        #[cfg(feature = "std")]
+        {
+            let error = std::io::Write::write(&mut hasher.clone(), &[0; 64]).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        let panic = std::panic::catch_unwind(|| hasher.chain_update([0; 64]));
+        assert!(panic.is_err(), "Hashed a message over the limit");
+    }
+
+    // The hashers can be serialised, and are validated when deserialised
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let hasher = Sha384::new().chain_update(b"hello ");
+        let json = serde_json::to_string(&hasher).unwrap();
+        let resumed: Sha384 = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            Sha384::digest(b"hello world"),
+            resumed.chain_update(b"world").finalize()
+        );
+        assert!(serde_json::from_str::<Sha512>(&json).is_err());
+    }
+}
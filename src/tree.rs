@@ -0,0 +1,339 @@
+//! Deterministic digests of whole directory trees
+//!
+//! Every regular file under the root is hashed, and the sorted list of (relative path, mode,
+//! file hash) entries is hashed again into a single tree digest. The digest only depends on the
+//! contents, paths and permissions of the files, not on the order the file system lists them in
+//! or on timestamps, so it can fingerprint build outputs:
+//!
+//! ```
+//! use sha_crypto::tree::{hash_tree, TreeOptions};
+//! use sha_crypto::Sha256;
+//!
+//! let mut options = TreeOptions::new();
+//! options.include("*.rs").unwrap();
+//! let digest = hash_tree::<Sha256>("src".as_ref(), &options).unwrap();
+//! ```
+
+use digest::{Digest, Output};
+use glob::{MatchOptions, Pattern};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use glob::PatternError;
+
+/// How the tree is walked, and which files are part of the digest
+#[derive(Clone, Default, Debug)]
+pub struct TreeOptions {
+    /// Hash the targets of symbolic links, instead of skipping the links
+    ///
+    /// Broken links, whose target doesn't exist, are still skipped.
+    pub follow_symlinks: bool,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl TreeOptions {
+    /// Options that skip symbolic links and include every regular file
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only include the files matching one of the patterns given to this method
+    ///
+    /// Patterns without a `/` are matched against the file name, and the others against the path
+    /// relative to the root. `*` and `?` don't match `/`, but `**` matches any number of
+    /// directories.
+    pub fn include(&mut self, pattern: &str) -> Result<&mut Self, PatternError> {
+        self.include.push(Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Leave out the files and directories matching the pattern, see [`Self::include`]
+    ///
+    /// Nothing under an excluded directory is included, even if it matches an include pattern.
+    pub fn exclude(&mut self, pattern: &str) -> Result<&mut Self, PatternError> {
+        self.exclude.push(Pattern::new(pattern)?);
+        Ok(self)
+    }
+}
+
+/// A file of the tree
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TreeEntry {
+    /// The path relative to the root, with `/` separating the components
+    pub path: Vec<u8>,
+    /// The permission bits of the file (0o644 or 0o444 outside of Unix)
+    pub mode: u32,
+    /// The hash of the file's contents
+    pub hash: Vec<u8>,
+}
+
+/// List and hash every regular file under `root`, sorted by path
+pub fn tree_entries<D: Digest>(root: &Path, options: &TreeOptions) -> io::Result<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+    let mut ancestors = Vec::new();
+    walk::<D>(root, &mut Vec::new(), options, &mut ancestors, &mut entries)?;
+    entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Combine the entries of a tree, in the order given, into the tree digest
+///
+/// Each entry is written as the length of the path (64-bit big endian), the path, the mode
+/// (32-bit big endian) and the hash of the file, and the digest is the hash of all of them.
+pub fn tree_digest<D: Digest>(entries: &[TreeEntry]) -> Output<D> {
+    let mut hasher = D::new();
+    for entry in entries {
+        hasher.update((entry.path.len() as u64).to_be_bytes());
+        hasher.update(&entry.path);
+        hasher.update(entry.mode.to_be_bytes());
+        hasher.update(&entry.hash);
+    }
+    hasher.finalize()
+}
+
+/// The digest of the tree under `root`, see [`tree_entries`] and [`tree_digest`]
+pub fn hash_tree<D: Digest>(root: &Path, options: &TreeOptions) -> io::Result<Output<D>> {
+    Ok(tree_digest::<D>(&tree_entries::<D>(root, options)?))
+}
+
+/// Hash the files of a directory and its subdirectories into `entries`
+///
+/// `relative` is the path of the directory relative to the root, with a trailing `/` unless it is
+/// the root. When following symbolic links, `ancestors` holds the directories being walked, to
+/// detect loops.
+fn walk<D: Digest>(
+    dir: &Path,
+    relative: &mut Vec<u8>,
+    options: &TreeOptions,
+    ancestors: &mut Vec<PathBuf>,
+    entries: &mut Vec<TreeEntry>,
+) -> io::Result<()> {
+    if options.follow_symlinks {
+        let canonical = fs::canonicalize(dir)?;
+        if ancestors.contains(&canonical) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: symbolic link loop", dir.display()),
+            ));
+        }
+        ancestors.push(canonical);
+    }
+
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+        let mut file_type = dir_entry.file_type()?;
+        let mut metadata = None;
+        if file_type.is_symlink() {
+            if !options.follow_symlinks {
+                continue;
+            }
+            let target = match fs::metadata(&path) {
+                Ok(target) => target,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            file_type = target.file_type();
+            metadata = Some(target);
+        }
+
+        let length = relative.len();
+        relative.extend_from_slice(dir_entry.file_name().as_encoded_bytes());
+        let name = String::from_utf8_lossy(dir_entry.file_name().as_encoded_bytes()).into_owned();
+        let matches = |patterns: &[Pattern]| {
+            let relative = String::from_utf8_lossy(relative);
+            patterns
+                .iter()
+                .any(|p| pattern_matches(p, &name, &relative))
+        };
+
+        if matches(&options.exclude) {
+            relative.truncate(length);
+            continue;
+        }
+        if file_type.is_dir() {
+            relative.push(b'/');
+            walk::<D>(&path, relative, options, ancestors, entries)?;
+        } else if file_type.is_file() && (options.include.is_empty() || matches(&options.include)) {
+            let metadata = match metadata {
+                Some(metadata) => metadata,
+                None => dir_entry.metadata()?,
+            };
+            entries.push(TreeEntry {
+                path: relative.clone(),
+                mode: mode(&metadata),
+                hash: crate::io::hash_file::<D>(&path)?.to_vec(),
+            });
+        }
+        relative.truncate(length);
+    }
+
+    if options.follow_symlinks {
+        ancestors.pop();
+    }
+    Ok(())
+}
+
+/// Match a pattern against the file name, or the relative path if the pattern has a `/`
+fn pattern_matches(pattern: &Pattern, name: &str, relative: &str) -> bool {
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    if pattern.as_str().contains('/') {
+        pattern.matches_with(relative, options)
+    } else {
+        pattern.matches_with(name, options)
+    }
+}
+
+/// The permission bits of a file
+#[cfg(unix)]
+fn mode(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+/// The permission bits of a file, approximated from the read-only flag
+#[cfg(not(unix))]
+fn mode(metadata: &Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+/// Tests for the tree digests
+#[cfg(test)]
+mod tests {
+    use super::{hash_tree, tree_digest, tree_entries, TreeEntry, TreeOptions};
+    use crate::test_util::hex;
+    use crate::test_util::ScratchDir;
+    use crate::Sha256;
+    use digest::Digest;
+    use std::fs;
+
+    /// Create a small tree in a scratch directory
+    fn scratch_tree(test: &str) -> ScratchDir {
+        let root = ScratchDir::new(&format!("tree-{}", test));
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("README"), "readme").unwrap();
+        fs::write(root.join("src/lib.rs"), "lib").unwrap();
+        fs::write(root.join("src/nested/mod.rs"), "mod").unwrap();
+        fs::write(root.join("src/notes.txt"), "notes").unwrap();
+        fs::write(root.join("target/out.o"), "object").unwrap();
+        root
+    }
+
+    // The entries are sorted by path, and the digest is their canonical encoding
+    #[test]
+    fn digest() {
+        let root = scratch_tree("digest");
+        let entries = tree_entries::<Sha256>(&root, &TreeOptions::new()).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.as_slice()).collect();
+        assert_eq!(
+            paths,
+            [
+                &b"README"[..],
+                b"src/lib.rs",
+                b"src/nested/mod.rs",
+                b"src/notes.txt",
+                b"target/out.o"
+            ]
+        );
+        assert_eq!(entries[1].hash, sha2::Sha256::digest("lib")[..]);
+
+        // The encoding of a single entry
+        let entry = TreeEntry {
+            path: b"a".to_vec(),
+            mode: 0o644,
+            hash: hex("ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb"),
+        };
+        let mut encoded = hex("0000000000000001");
+        encoded.push(b'a');
+        encoded.extend(hex("000001a4"));
+        encoded.extend(&entry.hash);
+        assert_eq!(
+            tree_digest::<Sha256>(&[entry]),
+            sha2::Sha256::digest(&encoded)
+        );
+    }
+
+    // The digest changes with the contents, the names and the permissions of the files
+    #[cfg(unix)]
+    #[test]
+    fn changes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = scratch_tree("changes");
+        let options = TreeOptions::new();
+        let original = hash_tree::<Sha256>(&root, &options).unwrap();
+        assert_eq!(hash_tree::<Sha256>(&root, &options).unwrap(), original);
+
+        fs::set_permissions(root.join("README"), fs::Permissions::from_mode(0o755)).unwrap();
+        let changed = hash_tree::<Sha256>(&root, &options).unwrap();
+        assert_ne!(changed, original);
+        fs::set_permissions(root.join("README"), fs::Permissions::from_mode(0o644)).unwrap();
+        fs::rename(root.join("README"), root.join("README2")).unwrap();
+        assert_ne!(hash_tree::<Sha256>(&root, &options).unwrap(), changed);
+    }
+
+    // Include and exclude patterns, against the file name or the relative path
+    #[test]
+    fn patterns() {
+        let root = scratch_tree("patterns");
+        let paths = |options: &TreeOptions| -> Vec<String> {
+            tree_entries::<Sha256>(&root, options)
+                .unwrap()
+                .into_iter()
+                .map(|e| String::from_utf8(e.path).unwrap())
+                .collect()
+        };
+
+        let mut options = TreeOptions::new();
+        options.include("*.rs").unwrap();
+        assert_eq!(paths(&options), ["src/lib.rs", "src/nested/mod.rs"]);
+        options.exclude("nested").unwrap();
+        assert_eq!(paths(&options), ["src/lib.rs"]);
+
+        let mut options = TreeOptions::new();
+        options.include("src/*").unwrap().exclude("target").unwrap();
+        assert_eq!(paths(&options), ["src/lib.rs", "src/notes.txt"]);
+        let mut options = TreeOptions::new();
+        options.include("src/**/*.rs").unwrap();
+        assert_eq!(paths(&options), ["src/lib.rs", "src/nested/mod.rs"]);
+
+        assert!(TreeOptions::new().include("[").is_err());
+    }
+
+    // Symbolic links are skipped, or followed with loops reported and broken links skipped
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        let root = scratch_tree("symlinks");
+        let skipped = hash_tree::<Sha256>(&root, &TreeOptions::new()).unwrap();
+        std::os::unix::fs::symlink("README", root.join("link")).unwrap();
+        std::os::unix::fs::symlink("src", root.join("src-link")).unwrap();
+        assert_eq!(
+            hash_tree::<Sha256>(&root, &TreeOptions::new()).unwrap(),
+            skipped
+        );
+
+        let mut options = TreeOptions::new();
+        options.follow_symlinks = true;
+        let entries = tree_entries::<Sha256>(&root, &options).unwrap();
+        assert_eq!(entries.len(), 9);
+        assert!(entries.iter().any(|e| e.path == b"src-link/nested/mod.rs"));
+        std::os::unix::fs::symlink("missing", root.join("broken")).unwrap();
+        assert_eq!(tree_entries::<Sha256>(&root, &options).unwrap(), entries);
+
+        std::os::unix::fs::symlink("..", root.join("src/loop")).unwrap();
+        assert!(tree_entries::<Sha256>(&root, &options).is_err());
+        assert!(tree_entries::<Sha256>(&root, &TreeOptions::new()).is_ok());
+    }
+}
@@ -0,0 +1,141 @@
+//! Runs the NIST CAVP response files in `tests/cavp` against every variant
+//!
+//! The files follow the SHAVS format: `ShortMsg` and `LongMsg` list messages with their digests,
+//! and `Monte` gives the seed and the checkpoints of the Monte Carlo test. Messages are hashed
+//! bit by bit, so both the byte-oriented and the bit-oriented files can be run.
+
+use sha_crypto::{Digest, Sha224, Sha256, Sha384, Sha512, Sha512_224, Sha512_256};
+use std::fs;
+use std::path::Path;
+
+/// An entry of a response file, its `key = value` lines in order
+type Entry = Vec<(String, String)>;
+
+/// Parse a response file into its entries, skipping comments and the `[L = ...]` header
+fn parse(name: &str) -> Vec<Entry> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/cavp")
+        .join(name);
+    let text = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e));
+
+    let mut entries = Vec::new();
+    let mut entry = Entry::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+        if line.is_empty() {
+            if !entry.is_empty() {
+                entries.push(std::mem::take(&mut entry));
+            }
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .unwrap_or_else(|| panic!("{}: invalid line {:?}", name, line));
+        entry.push((key.trim().to_string(), value.trim().to_string()));
+    }
+    if !entry.is_empty() {
+        entries.push(entry);
+    }
+    entries
+}
+
+/// The value of a field of an entry
+fn field<'a>(entry: &'a Entry, key: &str) -> &'a str {
+    entry
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+        .unwrap_or_else(|| panic!("Entry without {}: {:?}", key, entry))
+}
+
+/// Decode a hex string
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Check every message of a `ShortMsg` or `LongMsg` file
+///
+/// `hash` hashes the first `len` bits of a message.
+fn messages(name: &str, hash: impl Fn(&[u8], usize) -> Vec<u8>) {
+    let entries = parse(name);
+    assert!(!entries.is_empty(), "{} has no entries", name);
+    for entry in entries {
+        let len: usize = field(&entry, "Len").parse().unwrap();
+        // The empty message is written as a single zero byte, which update_bits leaves out
+        let msg = hex(field(&entry, "Msg"));
+        assert_eq!(
+            hash(&msg, len),
+            hex(field(&entry, "MD")),
+            "{}: Len = {}",
+            name,
+            len
+        );
+    }
+}
+
+/// Check the Monte Carlo test: each checkpoint is 1000 hashes of the three previous digests,
+/// starting from three copies of the seed or the previous checkpoint
+fn monte<D: Digest>(name: &str) {
+    let entries = parse(name);
+    let (seed, checkpoints) = entries.split_first().expect("Monte file without a seed");
+    let mut seed = hex(field(seed, "Seed"));
+    assert_eq!(
+        checkpoints.len(),
+        100,
+        "{} should have 100 checkpoints",
+        name
+    );
+
+    for entry in checkpoints {
+        let mut md = [seed.clone(), seed.clone(), seed];
+        for _ in 3..1003 {
+            let next = D::new()
+                .chain_update(&md[0])
+                .chain_update(&md[1])
+                .chain_update(&md[2])
+                .finalize()
+                .to_vec();
+            md.rotate_left(1);
+            md[2] = next;
+        }
+        let [_, _, checkpoint] = md;
+        assert_eq!(
+            checkpoint,
+            hex(field(entry, "MD")),
+            "{}: COUNT = {}",
+            name,
+            field(entry, "COUNT")
+        );
+        seed = checkpoint;
+    }
+}
+
+// Run the three files of a variant
+macro_rules! cavp_test {
+    ($test:ident, $hash:ty, $prefix:literal) => {
+        #[test]
+        fn $test() {
+            let hash = |msg: &[u8], len| {
+                let mut hasher = <$hash>::new();
+                hasher.update_bits(msg, len);
+                hasher.finalize().to_vec()
+            };
+            messages(concat!($prefix, "ShortMsg.rsp"), hash);
+            messages(concat!($prefix, "LongMsg.rsp"), hash);
+            monte::<$hash>(concat!($prefix, "Monte.rsp"));
+        }
+    };
+}
+
+cavp_test!(sha224, Sha224, "SHA224");
+cavp_test!(sha256, Sha256, "SHA256");
+cavp_test!(sha384, Sha384, "SHA384");
+cavp_test!(sha512, Sha512, "SHA512");
+cavp_test!(sha512_224, Sha512_224, "SHA512_224");
+cavp_test!(sha512_256, Sha512_256, "SHA512_256");
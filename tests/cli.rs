@@ -0,0 +1,365 @@
+//! Tests running the command-line tool
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// Run the tool with the given arguments and standard input
+fn run(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sha-crypto"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The tool may exit before reading everything, eg. on usage errors
+    let _ = child.stdin.take().unwrap().write_all(stdin);
+    child.wait_with_output().unwrap()
+}
+
+/// A scratch directory for the test's files, removed when dropped
+struct ScratchDir(PathBuf);
+
+impl std::ops::Deref for ScratchDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Create an empty scratch directory for the test's files
+fn scratch_dir(test: &str) -> ScratchDir {
+    let dir = std::env::temp_dir().join(format!("sha-crypto-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    ScratchDir(dir)
+}
+
+// Standard input is hashed when no file is given, or for "-"
+#[test]
+fn stdin() {
+    let expected = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9  -\n";
+    let output = run(&[], b"hello world");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    let output = run(&["-"], b"hello world");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
+
+// The lines follow the GNU format for every algorithm and both modes
+#[test]
+fn files() {
+    let dir = scratch_dir("files");
+    let path = dir.join("hello.txt");
+    std::fs::write(&path, "hello world").unwrap();
+    let path = path.to_str().unwrap();
+
+    for (algo, hash) in [
+        ("sha224", "2f05477fc24bb4faefd86517156dafdecec45b8ad3cf2522a563582b"),
+        ("sha384", "fdbd8e75a67f29f701a4e040385e2e23986303ea10239211af907fcbb83578b3e417cb71ce646efd0819dd8c088de1bd"),
+    ] {
+        let output = run(&["--algo", algo, path, "-b", "-"], b"");
+        assert!(output.status.success());
+        let empty = if algo == "sha224" {
+            "d14a028c2a3a2bc9476102bb288234c415a2b01f828ea62ac5b3e42f"
+        } else {
+            "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b"
+        };
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("{} *{}\n{} *-\n", hash, path, empty)
+        );
+    }
+}
+
+// Names with backslashes or line breaks are escaped, and the line starts with a backslash
+#[test]
+fn escaped_names() {
+    let dir = scratch_dir("escaped");
+    let path = dir.join("a\\b\nc");
+    std::fs::write(&path, "a").unwrap();
+    let escaped = dir.to_str().unwrap().to_string() + "/a\\\\b\\nc";
+
+    let output = run(&[path.to_str().unwrap()], b"");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "\\ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb  {}\n",
+            escaped
+        )
+    );
+    // Checking prints the names escaped the same way, also with only a backslash
+    let check = run(&["-c", "-"], &output.stdout);
+    assert_eq!(
+        String::from_utf8_lossy(&check.stdout),
+        format!("\\{}: OK\n", escaped)
+    );
+    let path = dir.join("a\\b");
+    std::fs::write(&path, "a").unwrap();
+    let output = run(&[path.to_str().unwrap()], b"");
+    let check = run(&["-c", "-"], &output.stdout);
+    assert_eq!(
+        String::from_utf8_lossy(&check.stdout),
+        format!("\\{}/a\\\\b: OK\n", dir.to_str().unwrap())
+    );
+}
+
+// Missing files are reported, the others are still hashed and the exit status is 1
+#[test]
+fn missing_file() {
+    let output = run(&["/nonexistent/file", "-"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "sha-crypto: /nonexistent/file: No such file or directory\n"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  -\n"
+    );
+}
+
+// Invalid options are usage errors
+#[test]
+fn usage_errors() {
+    for args in [&["--algo", "md5"][..], &["--algo"], &["--frobnicate"]] {
+        let output = run(args, b"");
+        assert_eq!(output.status.code(), Some(1), "Accepted {:?}", args);
+        assert!(output.stdout.is_empty());
+    }
+}
+
+// Checking a manifest reports every file, and a summary of the problems
+#[test]
+fn check() {
+    let dir = scratch_dir("check");
+    std::fs::write(dir.join("a"), "a").unwrap();
+    std::fs::write(dir.join("b"), "b").unwrap();
+    let a = dir.join("a").to_str().unwrap().to_string();
+    let b = dir.join("b").to_str().unwrap().to_string();
+    let missing = dir.join("missing").to_str().unwrap().to_string();
+    let hash_a = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+    let hash_b = "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d";
+    let manifest = format!(
+        "{hash_a}  {a}\n# comment\n\ngarbage\n{hash_b}  {a}\n{hash_a}  {missing}\nSHA256 ({b}) = {hash_b}\n"
+    );
+
+    let output = run(&["-c", "-"], manifest.as_bytes());
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{a}: OK\n{a}: FAILED\n{missing}: FAILED open or read\n{b}: OK\n")
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "sha-crypto: {missing}: No such file or directory\n\
+             sha-crypto: WARNING: 1 line is improperly formatted\n\
+             sha-crypto: WARNING: 1 listed file could not be read\n\
+             sha-crypto: WARNING: 1 computed checksum did NOT match\n"
+        )
+    );
+
+    // --quiet only hides the successes, --status hides everything but read errors
+    let output = run(&["--check", "--quiet"], manifest.as_bytes());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{a}: FAILED\n{missing}: FAILED open or read\n")
+    );
+    let output = run(&["-c", "--status"], manifest.as_bytes());
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("sha-crypto: {missing}: No such file or directory\n")
+    );
+}
+
+// The exit status follows coreutils for the stricter options and empty manifests
+#[test]
+fn check_status() {
+    let dir = scratch_dir("check-status");
+    std::fs::write(dir.join("a"), "a").unwrap();
+    let a = dir.join("a").to_str().unwrap().to_string();
+    let missing = dir.join("missing").to_str().unwrap().to_string();
+    let hash_a = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+
+    // Improperly formatted lines only fail with --strict
+    let manifest = format!("{hash_a} *{a}\ngarbage\n");
+    assert_eq!(run(&["-c"], manifest.as_bytes()).status.code(), Some(0));
+    let output = run(&["-c", "--strict"], manifest.as_bytes());
+    assert_eq!(output.status.code(), Some(1));
+
+    // Missing files are skipped with --ignore-missing, as long as something was verified
+    let manifest = format!("{hash_a}  {a}\n{hash_a}  {missing}\n");
+    let output = run(&["-c", "--ignore-missing"], manifest.as_bytes());
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{a}: OK\n")
+    );
+    let manifest = format!("{hash_a}  {missing}\n");
+    let output = run(&["-c", "--ignore-missing"], manifest.as_bytes());
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "sha-crypto: standard input: no file was verified\n"
+    );
+
+    // A manifest without any checksum lines fails
+    let output = run(&["-c"], b"garbage\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "sha-crypto: standard input: no properly formatted checksum lines found\n"
+    );
+
+    // With --algo, lines for other algorithms are improperly formatted
+    let manifest = format!("{hash_a}  {a}\n");
+    let output = run(&["-c", "--algo", "sha512"], manifest.as_bytes());
+    assert_eq!(output.status.code(), Some(1));
+
+    // The check options need --check, and the modes don't make sense with it
+    assert_eq!(run(&["--quiet"], b"").status.code(), Some(1));
+    assert_eq!(run(&["-c", "-b"], b"").status.code(), Some(1));
+}
+
+// --tree prints the library's tree digest of each directory
+#[test]
+fn tree() {
+    use sha_crypto::tree::{hash_tree, TreeOptions};
+
+    let dir = scratch_dir("tree");
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.txt"), "a").unwrap();
+    std::fs::write(dir.join("b.log"), "b").unwrap();
+    std::fs::write(dir.join("sub/c.txt"), "c").unwrap();
+    let path = dir.to_str().unwrap();
+
+    let mut options = TreeOptions::new();
+    options.include("*.txt").unwrap();
+    let expected = hash_tree::<sha_crypto::Sha384>(&dir, &options).unwrap();
+    let expected: String = expected.iter().map(|b| format!("{:02x}", b)).collect();
+    let output = run(
+        &["--algo=sha384", "--include", "*.txt", "--tree", path],
+        b"",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}  {}\n", expected, path)
+    );
+
+    let output = run(&["--include", "[", "--tree", path], b"");
+    assert_eq!(output.status.code(), Some(1));
+    let output = run(&["--tree", "/nonexistent/dir"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    // The tree options need --tree, and don't mix with files or checking
+    for args in [
+        &["--include", "*.txt", path][..],
+        &["-L", path],
+        &["--tree", path, path],
+        &["-c", "--tree", path],
+    ] {
+        let output = run(args, b"");
+        assert_eq!(output.status.code(), Some(1), "Accepted {:?}", args);
+        assert!(output.stdout.is_empty());
+    }
+}
+
+// A file named "tree" is hashed like any other
+#[test]
+fn file_named_tree() {
+    let dir = scratch_dir("file-named-tree");
+    std::fs::write(dir.join("tree"), "a").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_sha-crypto"))
+        .arg("tree")
+        .current_dir(&*dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb  tree\n"
+    );
+}
+
+// Files hashed on several jobs are printed in the order they were given, around standard input
+#[test]
+fn jobs() {
+    let dir = scratch_dir("jobs");
+    let mut files = Vec::new();
+    for i in 0..20 {
+        let path = dir.join(format!("file{}", i));
+        std::fs::write(&path, vec![i as u8; i * 10_000]).unwrap();
+        files.push(path.to_str().unwrap().to_string());
+    }
+    files.insert(5, "-".to_string());
+    files.insert(9, "/nonexistent/file".to_string());
+    let args: Vec<&str> = files.iter().map(String::as_str).collect();
+
+    let sequential = run(&args, b"stdin");
+    assert_eq!(sequential.status.code(), Some(1));
+    for jobs in ["-j4", "--jobs=3"] {
+        let parallel = run(&[&[jobs][..], &args].concat(), b"stdin");
+        assert_eq!(parallel.status.code(), Some(1));
+        assert_eq!(parallel.stdout, sequential.stdout);
+        assert_eq!(parallel.stderr, sequential.stderr);
+    }
+    let lines = String::from_utf8(sequential.stdout).unwrap();
+    assert_eq!(lines.lines().count(), 21);
+    assert!(lines.lines().nth(5).unwrap().ends_with("  -"));
+
+    for jobs in [&["-j0"][..], &["-j", "x"], &["--jobs"]] {
+        assert_eq!(run(jobs, b"").status.code(), Some(1), "Accepted {:?}", jobs);
+    }
+}
+
+// Memory mapped files hash like the files read, and the option needs the mmap feature
+#[test]
+fn mmap() {
+    let dir = scratch_dir("mmap");
+    let (file, empty) = (dir.join("file"), dir.join("empty"));
+    std::fs::write(&file, vec![7u8; 300_000]).unwrap();
+    std::fs::write(&empty, b"").unwrap();
+    let args = [
+        file.to_str().unwrap(),
+        empty.to_str().unwrap(),
+        "-",
+        "/dev/null",
+    ];
+
+    let read = run(&args, b"stdin");
+    assert_eq!(read.status.code(), Some(0));
+    let mapped = run(&[&["--mmap"][..], &args].concat(), b"stdin");
+    if cfg!(feature = "mmap") {
+        assert_eq!(mapped.status.code(), Some(0));
+        assert_eq!(mapped.stdout, read.stdout);
+        let parallel = run(&[&["--mmap", "-j2"][..], &args].concat(), b"stdin");
+        assert_eq!(parallel.stdout, read.stdout);
+        let check = run(&["--mmap", "--check", "-"], &read.stdout);
+        assert_eq!(check.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8(check.stdout)
+                .unwrap()
+                .matches(": OK")
+                .count(),
+            3
+        );
+    } else {
+        assert_eq!(mapped.status.code(), Some(1));
+        assert!(String::from_utf8(mapped.stderr)
+            .unwrap()
+            .contains("mmap feature"));
+    }
+}
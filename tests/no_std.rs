@@ -0,0 +1,77 @@
+//! Building the library without std, as firmware and WASM users do
+
+use std::path::Path;
+use std::process::Command;
+
+/// A target without std, built for when its standard library is installed
+const NO_STD_TARGET: &str = "thumbv7em-none-eabi";
+
+// The library links into the no_std crate in tests/no-std-check, alone and with the features that
+// only need an allocator. That crate defines a panic handler, so on the host the build fails if
+// anything brings in std. This is only an approximation of a real no_std target, which is also
+// built when its standard library is installed (`rustup target add thumbv7em-none-eabi`).
+#[test]
+fn no_std() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR")).join("no-std");
+
+    // Build a copy of the crate, so that its lockfile isn't written to the source tree, resolving
+    // the same dependency versions as the library itself
+    let check = tmp.join("no-std-check");
+    std::fs::create_dir_all(check.join("src")).unwrap();
+    let manifest = std::fs::read_to_string(root.join("tests/no-std-check/Cargo.toml")).unwrap();
+    let manifest = manifest.replace("\"../..\"", &format!("{:?}", root));
+    std::fs::write(check.join("Cargo.toml"), manifest).unwrap();
+    std::fs::copy(
+        root.join("tests/no-std-check/src/lib.rs"),
+        check.join("src/lib.rs"),
+    )
+    .unwrap();
+    if root.join("Cargo.lock").exists() {
+        std::fs::copy(root.join("Cargo.lock"), check.join("Cargo.lock")).unwrap();
+    }
+
+    let mut targets = vec![None];
+    if target_installed(NO_STD_TARGET) {
+        targets.push(Some(NO_STD_TARGET));
+    } else {
+        eprintln!(
+            "{} isn't installed, only building for the host",
+            NO_STD_TARGET
+        );
+    }
+
+    for target in targets {
+        for features in ["", "alloc", "alloc,serde"] {
+            let mut command = Command::new(env!("CARGO"));
+            command
+                .args(["build", "--features", features, "--manifest-path"])
+                .arg(check.join("Cargo.toml"))
+                .env("CARGO_TARGET_DIR", tmp.join("target"));
+            if let Some(target) = target {
+                command.args(["--target", target]);
+            }
+            let output = command.output().unwrap();
+            assert!(
+                output.status.success(),
+                "Failed to build for {} with features {:?}:\n{}",
+                target.unwrap_or("the host"),
+                features,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+}
+
+/// Whether the standard library of `target` is installed for the current toolchain
+fn target_installed(target: &str) -> bool {
+    let output = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()
+        .unwrap();
+    let sysroot = String::from_utf8(output.stdout).unwrap();
+    Path::new(sysroot.trim())
+        .join("lib/rustlib")
+        .join(target)
+        .exists()
+}